@@ -0,0 +1,345 @@
+use crate::coding;
+use crate::comparator::Comparator;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+// entry layout:
+// varint shared key len | varint unshared key len | varint value len |
+// unshared key bytes | value
+//
+// every restart_interval entries the key is stored whole (shared = 0),
+// the offsets of those restart points follow the entries as u32s,
+// followed by their count
+pub struct BlockBuilder {
+    buf: Vec<u8>,
+    restarts: Vec<u32>,
+    // entries since the last restart point
+    counter: usize,
+    restart_interval: usize,
+    last_key: Vec<u8>,
+}
+
+impl BlockBuilder {
+    pub fn new(restart_interval: usize) -> Self {
+        Self {
+            buf: vec![],
+            restarts: vec![0],
+            counter: 0,
+            restart_interval,
+            last_key: vec![],
+        }
+    }
+
+    // keys must be added in increasing order
+    pub fn add(&mut self, key: &[u8], value: &[u8]) {
+        let mut shared = 0;
+        if self.counter < self.restart_interval {
+            shared = self
+                .last_key
+                .iter()
+                .zip(key)
+                .take_while(|(a, b)| a == b)
+                .count();
+        } else {
+            self.restarts.push(self.buf.len() as u32);
+            self.counter = 0;
+        }
+        coding::put_varint(&mut self.buf, shared as u64);
+        coding::put_varint(&mut self.buf, (key.len() - shared) as u64);
+        coding::put_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(&key[shared..]);
+        self.buf.extend_from_slice(value);
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.counter += 1;
+    }
+
+    // appends the restart array and returns the block contents,
+    // the builder is ready for a new block afterwards
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut block = std::mem::take(&mut self.buf);
+        for restart in &self.restarts {
+            block.extend_from_slice(&restart.to_le_bytes());
+        }
+        block.extend_from_slice(&(self.restarts.len() as u32).to_le_bytes());
+        self.restarts = vec![0];
+        self.counter = 0;
+        self.last_key.clear();
+        block
+    }
+
+    pub fn size_estimate(&self) -> usize {
+        self.buf.len() + 4 * self.restarts.len() + 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+pub struct Block {
+    data: Vec<u8>,
+    // offset of the restart array
+    restarts: usize,
+    num_restarts: usize,
+}
+
+impl Block {
+    // None if the restart array doesn't fit in data
+    pub fn new(data: Vec<u8>) -> Option<Self> {
+        let len = data.len().checked_sub(4)?;
+        let num_restarts =
+            u32::from_le_bytes(data[len..].try_into().unwrap()) as usize;
+        let restarts = len.checked_sub(num_restarts.checked_mul(4)?)?;
+        if num_restarts == 0 {
+            return None;
+        }
+        Some(Self {
+            data,
+            restarts,
+            num_restarts,
+        })
+    }
+
+    // bytes it takes up
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn restart_point(&self, i: usize) -> usize {
+        let offset = self.restarts + 4 * i;
+        u32::from_le_bytes(self.data[offset..offset + 4].try_into().unwrap())
+            as usize
+    }
+
+    // decodes the entry header at offset,
+    // returns (shared, unshared, value len, header len)
+    fn decode_entry(
+        &self,
+        offset: usize,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let data = self.data.get(offset..self.restarts)?;
+        let (shared, n0) = coding::get_varint(data)?;
+        let (unshared, n1) = coding::get_varint(&data[n0..])?;
+        let (value_len, n2) = coding::get_varint(&data[n0 + n1..])?;
+        let header_len = n0 + n1 + n2;
+        let body_len = unshared.checked_add(value_len)?;
+        if ((data.len() - header_len) as u64) < body_len {
+            return None;
+        }
+        Some((
+            shared as usize,
+            unshared as usize,
+            value_len as usize,
+            header_len,
+        ))
+    }
+}
+
+// cursor over the entries of a block
+pub struct BlockIter {
+    block: Arc<Block>,
+    // the order the keys were added in
+    comparator: Arc<dyn Comparator>,
+    // offset of the current entry, block.restarts when not valid
+    current: usize,
+    // offset of the entry after the current one
+    next: usize,
+    // restart point at or before current
+    restart_index: usize,
+    key: Vec<u8>,
+    value: (usize, usize),
+    corrupted: bool,
+}
+
+impl BlockIter {
+    pub fn new(block: Arc<Block>, comparator: Arc<dyn Comparator>) -> Self {
+        let restarts = block.restarts;
+        Self {
+            block,
+            comparator,
+            current: restarts,
+            next: restarts,
+            restart_index: 0,
+            key: vec![],
+            value: (0, 0),
+            corrupted: false,
+        }
+    }
+
+    pub fn valid(&self) -> bool {
+        self.current < self.block.restarts
+    }
+
+    // set when an entry failed to decode, the cursor is invalid then
+    pub fn corrupted(&self) -> bool {
+        self.corrupted
+    }
+
+    pub fn key(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        &self.key
+    }
+
+    pub fn value(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        &self.block.data[self.value.0..self.value.1]
+    }
+
+    pub fn seek_to_first(&mut self) {
+        self.seek_to_restart(0);
+        self.parse_next();
+    }
+
+    pub fn seek_to_last(&mut self) {
+        self.seek_to_restart(self.block.num_restarts - 1);
+        while self.parse_next() && self.next < self.block.restarts {}
+    }
+
+    // positions at the first entry with a key >= target
+    pub fn seek(&mut self, target: &[u8]) {
+        // binary search for the last restart point with a key < target
+        let mut left = 0;
+        let mut right = self.block.num_restarts - 1;
+        while left < right {
+            let mid = (left + right).div_ceil(2);
+            let offset = self.block.restart_point(mid);
+            let key = match self.block.decode_entry(offset) {
+                Some((0, unshared, _, header_len)) => {
+                    let start = offset + header_len;
+                    &self.block.data[start..start + unshared]
+                }
+                _ => return self.corrupt(),
+            };
+            if self.comparator.compare(key, target) == Ordering::Less {
+                left = mid;
+            } else {
+                right = mid - 1;
+            }
+        }
+        self.seek_to_restart(left);
+        while self.parse_next() {
+            if self.comparator.compare(&self.key, target) != Ordering::Less {
+                return;
+            }
+        }
+    }
+
+    pub fn next(&mut self) {
+        debug_assert!(self.valid());
+        self.parse_next();
+    }
+
+    // entries only decode front to back, so this goes back to the
+    // restart point before the current entry and walks up to it
+    pub fn prev(&mut self) {
+        debug_assert!(self.valid());
+        let original = self.current;
+        while self.block.restart_point(self.restart_index) >= original {
+            if self.restart_index == 0 {
+                // the current entry is the first one
+                self.current = self.block.restarts;
+                self.next = self.block.restarts;
+                return;
+            }
+            self.restart_index -= 1;
+        }
+        self.seek_to_restart(self.restart_index);
+        while self.parse_next() && self.next < original {}
+    }
+
+    fn seek_to_restart(&mut self, index: usize) {
+        self.key.clear();
+        self.restart_index = index;
+        self.next = self.block.restart_point(index);
+    }
+
+    fn parse_next(&mut self) -> bool {
+        self.current = self.next;
+        if self.current >= self.block.restarts {
+            self.current = self.block.restarts;
+            return false;
+        }
+        let (shared, unshared, value_len, header_len) =
+            match self.block.decode_entry(self.current) {
+                Some(entry) if entry.0 <= self.key.len() => entry,
+                _ => {
+                    self.corrupt();
+                    return false;
+                }
+            };
+        let start = self.current + header_len;
+        self.key.truncate(shared);
+        self.key
+            .extend_from_slice(&self.block.data[start..start + unshared]);
+        self.value = (start + unshared, start + unshared + value_len);
+        self.next = self.value.1;
+        while self.restart_index + 1 < self.block.num_restarts
+            && self.block.restart_point(self.restart_index + 1) < self.current
+        {
+            self.restart_index += 1;
+        }
+        true
+    }
+
+    fn corrupt(&mut self) {
+        self.corrupted = true;
+        self.current = self.block.restarts;
+        self.next = self.block.restarts;
+    }
+}
+
+#[test]
+fn test_block() {
+    use crate::comparator::BytewiseComparator;
+
+    let mut builder = BlockBuilder::new(4);
+    let keys = (0..100)
+        .map(|i| format!("key{:03}", i * 2).into_bytes())
+        .collect::<Vec<_>>();
+    for key in &keys {
+        builder.add(key, &key[3..]);
+    }
+    let block = Arc::new(Block::new(builder.finish()).unwrap());
+
+    let comparator = Arc::new(BytewiseComparator);
+    let mut iter = BlockIter::new(block.clone(), comparator.clone());
+    iter.seek_to_first();
+    for key in &keys {
+        assert!(iter.valid());
+        assert_eq!(iter.key(), key);
+        assert_eq!(iter.value(), &key[3..]);
+        iter.next();
+    }
+    assert!(!iter.valid());
+
+    iter.seek_to_last();
+    for key in keys.iter().rev() {
+        assert!(iter.valid());
+        assert_eq!(iter.key(), key);
+        assert_eq!(iter.value(), &key[3..]);
+        iter.prev();
+    }
+    assert!(!iter.valid());
+
+    iter.seek(b"key050");
+    assert_eq!(iter.key(), b"key050");
+    iter.seek(b"key051");
+    assert_eq!(iter.key(), b"key052");
+    iter.prev();
+    assert_eq!(iter.key(), b"key050");
+    iter.seek(b"a");
+    assert_eq!(iter.key(), b"key000");
+    iter.seek(b"z");
+    assert!(!iter.valid());
+    assert!(!iter.corrupted());
+
+    let empty = Arc::new(Block::new(BlockBuilder::new(4).finish()).unwrap());
+    let mut iter = BlockIter::new(empty, comparator);
+    iter.seek_to_first();
+    assert!(!iter.valid());
+    iter.seek_to_last();
+    assert!(!iter.valid());
+    iter.seek(b"a");
+    assert!(!iter.valid());
+}
@@ -0,0 +1,189 @@
+use crate::block::Block;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, Mutex};
+
+// each shard has a lock of its own, so readers of different blocks
+// seldom wait on each other
+const NUM_SHARDS: usize = 16;
+
+// a table's file number and the offset of the block in it
+type BlockKey = (u64, u64);
+
+// how the block cache has been doing since the database was opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // bytes of blocks held, never more than the capacity
+    pub usage: usize,
+    pub capacity: usize,
+}
+
+// the data blocks read from tables, up to capacity bytes of them
+// across every table, split into shards by key that each drop
+// the block used the longest ago to make room
+pub struct BlockCache {
+    shards: Vec<Mutex<Shard>>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Shard {
+    // along with when each was last used
+    blocks: HashMap<BlockKey, (Arc<Block>, u64)>,
+    // the keys by when they were last used, oldest first
+    lru: BTreeMap<u64, BlockKey>,
+    clock: u64,
+    usage: usize,
+    capacity: usize,
+}
+
+impl Shard {
+    fn touch(&mut self, key: BlockKey) -> Option<Arc<Block>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let (block, used) = self.blocks.get_mut(&key)?;
+        self.lru.remove(used);
+        *used = clock;
+        self.lru.insert(clock, key);
+        Some(block.clone())
+    }
+
+    fn insert(&mut self, key: BlockKey, block: Arc<Block>) {
+        self.remove(key);
+        // a block bigger than the whole shard isn't worth
+        // evicting everything else for
+        if block.size() > self.capacity {
+            return;
+        }
+        self.usage += block.size();
+        while self.usage > self.capacity {
+            let (_, oldest) = self.lru.pop_first().unwrap();
+            let (evicted, _) = self.blocks.remove(&oldest).unwrap();
+            self.usage -= evicted.size();
+        }
+        self.clock += 1;
+        self.lru.insert(self.clock, key);
+        self.blocks.insert(key, (block, self.clock));
+    }
+
+    fn remove(&mut self, key: BlockKey) {
+        if let Some((block, used)) = self.blocks.remove(&key) {
+            self.lru.remove(&used);
+            self.usage -= block.size();
+        }
+    }
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            // the remainder goes a byte each to the first shards, so
+            // they add up to the capacity exactly
+            shards: (0..NUM_SHARDS)
+                .map(|i| {
+                    Mutex::new(Shard {
+                        capacity: capacity / NUM_SHARDS
+                            + usize::from(i < capacity % NUM_SHARDS),
+                        ..Shard::default()
+                    })
+                })
+                .collect(),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, key: BlockKey) -> &Mutex<Shard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % NUM_SHARDS]
+    }
+
+    pub fn get(&self, file_number: u64, offset: u64) -> Option<Arc<Block>> {
+        let key = (file_number, offset);
+        let block = self.shard(key).lock().unwrap().touch(key);
+        let counter = match block {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, atomic::Ordering::Relaxed);
+        block
+    }
+
+    pub fn insert(&self, file_number: u64, offset: u64, block: Arc<Block>) {
+        let key = (file_number, offset);
+        self.shard(key).lock().unwrap().insert(key, block);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let usage = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().usage)
+            .sum();
+        CacheStats {
+            hits: self.hits.load(atomic::Ordering::Relaxed),
+            misses: self.misses.load(atomic::Ordering::Relaxed),
+            usage,
+            capacity: self.capacity,
+        }
+    }
+}
+
+#[test]
+fn test_block_cache() {
+    // 4 restarts and their count, 20 bytes a block
+    let block = || {
+        Arc::new(Block::new([vec![0; 16], vec![4, 0, 0, 0]].concat()).unwrap())
+    };
+    let cache = BlockCache::new(NUM_SHARDS * 40);
+    assert!(cache.get(1, 0).is_none());
+    cache.insert(1, 0, block());
+    assert!(cache.get(1, 0).is_some());
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            hits: 1,
+            misses: 1,
+            usage: 20,
+            capacity: NUM_SHARDS * 40,
+        }
+    );
+
+    // a single shard holds two blocks, the one used the longest
+    // ago goes first
+    let mut shard = Shard {
+        capacity: 40,
+        ..Shard::default()
+    };
+    shard.insert((1, 0), block());
+    shard.insert((1, 20), block());
+    assert!(shard.touch((1, 0)).is_some());
+    shard.insert((1, 40), block());
+    assert!(shard.touch((1, 20)).is_none());
+    assert!(shard.touch((1, 0)).is_some());
+    assert!(shard.touch((1, 40)).is_some());
+    assert_eq!(shard.usage, 40);
+
+    // too big to be kept at all
+    let big =
+        Arc::new(Block::new([vec![0; 60], vec![1, 0, 0, 0]].concat()).unwrap());
+    shard.insert((2, 0), big);
+    assert!(shard.touch((2, 0)).is_none());
+    assert_eq!(shard.usage, 40);
+
+    // full shards never hold more than the capacity between them
+    let capacity = NUM_SHARDS * 40 - 1;
+    let cache = BlockCache::new(capacity);
+    for offset in 0..NUM_SHARDS as u64 * 10 {
+        cache.insert(1, offset * 20, block());
+    }
+    assert!(cache.stats().usage <= capacity);
+}
@@ -0,0 +1,83 @@
+use std::io::{self, Read};
+
+// LEB128, 7 bits per byte, high bit set on every byte but the last
+pub fn put_varint(dst: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        dst.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    dst.push(v as u8);
+}
+
+pub fn varint_len(mut v: u64) -> usize {
+    let mut len = 1;
+    while v >= 0x80 {
+        v >>= 7;
+        len += 1;
+    }
+    len
+}
+
+pub fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut v = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        r.read_exact(&mut byte)?;
+        v |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+// decodes a varint from the front of src,
+// returns it along with the number of bytes it took
+pub fn get_varint(src: &[u8]) -> Option<(u64, usize)> {
+    let mut v = 0;
+    for (i, byte) in src.iter().take(10).enumerate() {
+        v |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((v, i + 1));
+        }
+    }
+    None
+}
+
+pub fn put_length_prefixed(dst: &mut Vec<u8>, data: &[u8]) {
+    put_varint(dst, data.len() as u64);
+    dst.extend_from_slice(data);
+}
+
+// decodes a varint length followed by that many bytes from the front
+// of src, returns the bytes along with the number of bytes it all took
+pub fn get_length_prefixed(src: &[u8]) -> Option<(&[u8], usize)> {
+    let (len, n) = get_varint(src)?;
+    let end = n.checked_add(usize::try_from(len).ok()?)?;
+    Some((src.get(n..end)?, end))
+}
+
+#[test]
+fn test_varint() {
+    for v in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+        let mut buf = vec![];
+        put_varint(&mut buf, v);
+        assert_eq!(buf.len(), varint_len(v));
+        assert_eq!(read_varint(&mut buf.as_slice()).unwrap(), v);
+        assert_eq!(get_varint(&buf), Some((v, buf.len())));
+        assert_eq!(get_varint(&buf[..buf.len() - 1]), None);
+    }
+}
+
+#[test]
+fn test_length_prefixed() {
+    let mut buf = vec![];
+    put_length_prefixed(&mut buf, b"abc");
+    put_length_prefixed(&mut buf, b"");
+    assert_eq!(get_length_prefixed(&buf), Some((&b"abc"[..], 4)));
+    assert_eq!(get_length_prefixed(&buf[4..]), Some((&b""[..], 1)));
+    assert_eq!(get_length_prefixed(&buf[..3]), None);
+}
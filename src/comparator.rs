@@ -0,0 +1,86 @@
+use std::cmp::Ordering;
+
+// a total order over keys
+//
+// the name is stored along with the database, which refuses to open
+// under a comparator with any other name, so it must change whenever
+// the order does
+pub trait Comparator: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+
+    // may shorten start to any key in [start, limit), index blocks
+    // only need a key separating two blocks, not a real one
+    fn find_shortest_separator(&self, _start: &mut Vec<u8>, _limit: &[u8]) {}
+
+    // may change key to any shorter key >= key
+    fn find_short_successor(&self, _key: &mut Vec<u8>) {}
+
+    // true only for the order of BytewiseComparator, the one that
+    // keeps the keys starting with any prefix next to each other
+    fn is_bytewise(&self) -> bool {
+        false
+    }
+}
+
+// plain lexicographic order of the bytes
+pub struct BytewiseComparator;
+
+impl Comparator for BytewiseComparator {
+    fn name(&self) -> &str {
+        "reberu.BytewiseComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn is_bytewise(&self) -> bool {
+        true
+    }
+
+    fn find_shortest_separator(&self, start: &mut Vec<u8>, limit: &[u8]) {
+        let shared =
+            start.iter().zip(limit).take_while(|(a, b)| a == b).count();
+        // one is a prefix of the other, nothing to cut
+        if shared >= start.len().min(limit.len()) {
+            return;
+        }
+        let byte = start[shared];
+        if byte < 0xff && byte + 1 < limit[shared] {
+            start[shared] += 1;
+            start.truncate(shared + 1);
+        }
+    }
+
+    fn find_short_successor(&self, key: &mut Vec<u8>) {
+        // bump the first byte that can be, dropping the rest
+        if let Some(i) = key.iter().position(|byte| *byte != 0xff) {
+            key[i] += 1;
+            key.truncate(i + 1);
+        }
+    }
+}
+
+#[test]
+fn test_bytewise_comparator() {
+    let separator = |start: &[u8], limit: &[u8]| {
+        let mut start = start.to_vec();
+        BytewiseComparator.find_shortest_separator(&mut start, limit);
+        start
+    };
+    assert_eq!(separator(b"abcdef", b"abzz"), b"abd");
+    assert_eq!(separator(b"abc", b"abcdef"), b"abc");
+    assert_eq!(separator(b"abc1", b"abc2"), b"abc1");
+    assert_eq!(separator(b"a\xff", b"b"), b"a\xff");
+
+    let successor = |key: &[u8]| {
+        let mut key = key.to_vec();
+        BytewiseComparator.find_short_successor(&mut key);
+        key
+    };
+    assert_eq!(successor(b"abc"), b"b");
+    assert_eq!(successor(b"\xff\xffz"), b"\xff\xff{");
+    assert_eq!(successor(b"\xff\xff"), b"\xff\xff");
+}
@@ -0,0 +1,158 @@
+use crate::coding;
+
+// compresses the data blocks of new tables, each block records the id
+// of the compressor that made it, 0 for a block stored as is
+//
+// a table is only readable by a database opened with a compressor of
+// the same id as the one it was written with, or the built-in one
+pub trait Compressor: Send + Sync {
+    fn name(&self) -> &str;
+    // never 0, and different for every compressor in use, 1 is
+    // taken by LzCompressor
+    fn id(&self) -> u8;
+    fn compress(&self, input: &[u8]) -> Vec<u8>;
+    // None if input is not something compress made
+    fn decompress(&self, input: &[u8]) -> Option<Vec<u8>>;
+}
+
+// shortest match worth a copy instead of the bytes themselves
+const MIN_MATCH: usize = 4;
+// longest literal run and match a single tag can hold
+const MAX_LITERAL: usize = 0x80;
+const MAX_MATCH: usize = MIN_MATCH + 0x7f;
+const HASH_BITS: u32 = 14;
+
+// a small LZ77, good at the repeated keys, prefixes and JSON field
+// names blocks are full of
+//
+// layout:
+// varint uncompressed len | op*
+//
+// an op is a tag byte, with the high bit clear it is followed by
+// (tag + 1) literal bytes, with it set it copies (tag & 0x7f) + 4
+// bytes starting varint offset bytes back in the output
+pub struct LzCompressor;
+
+impl LzCompressor {
+    pub const ID: u8 = 1;
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = u32::from_le_bytes(bytes[..MIN_MATCH].try_into().unwrap());
+    (v.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_BITS)) as usize
+}
+
+fn put_literals(dst: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERAL) {
+        dst.push((chunk.len() - 1) as u8);
+        dst.extend_from_slice(chunk);
+    }
+}
+
+impl Compressor for LzCompressor {
+    fn name(&self) -> &str {
+        "reberu.LzCompressor"
+    }
+
+    fn id(&self) -> u8 {
+        Self::ID
+    }
+
+    fn compress(&self, input: &[u8]) -> Vec<u8> {
+        let mut dst = Vec::with_capacity(input.len() / 2 + 10);
+        coding::put_varint(&mut dst, input.len() as u64);
+        // where each hash of 4 bytes was last seen, plus one
+        let mut table = vec![0; 1 << HASH_BITS];
+        let mut literal_start = 0;
+        let mut i = 0;
+        while i + MIN_MATCH <= input.len() {
+            let h = hash(&input[i..]);
+            let candidate = table[h];
+            table[h] = i + 1;
+            let Some(start) = candidate.checked_sub(1) else {
+                i += 1;
+                continue;
+            };
+            let len = input[start..]
+                .iter()
+                .zip(&input[i..])
+                .take(MAX_MATCH)
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len < MIN_MATCH {
+                i += 1;
+                continue;
+            }
+            put_literals(&mut dst, &input[literal_start..i]);
+            dst.push(0x80 | (len - MIN_MATCH) as u8);
+            coding::put_varint(&mut dst, (i - start) as u64);
+            i += len;
+            literal_start = i;
+        }
+        put_literals(&mut dst, &input[literal_start..]);
+        dst
+    }
+
+    fn decompress(&self, input: &[u8]) -> Option<Vec<u8>> {
+        let (len, mut i) = coding::get_varint(input)?;
+        let len = usize::try_from(len).ok()?;
+        // the length may be garbage, don't trust it with an allocation
+        let mut dst = Vec::with_capacity(len.min(input.len() * MAX_MATCH));
+        while i < input.len() {
+            let tag = input[i] as usize;
+            i += 1;
+            if tag & 0x80 == 0 {
+                let literals = input.get(i..i + tag + 1)?;
+                dst.extend_from_slice(literals);
+                i += tag + 1;
+            } else {
+                let (offset, n) = coding::get_varint(&input[i..])?;
+                i += n;
+                if offset == 0 {
+                    return None;
+                }
+                let start = dst.len().checked_sub(offset as usize)?;
+                // the copy may overlap the bytes it is making
+                for j in 0..(tag & 0x7f) + MIN_MATCH {
+                    dst.push(dst[start + j]);
+                }
+            }
+            if dst.len() > len {
+                return None;
+            }
+        }
+        (dst.len() == len).then_some(dst)
+    }
+}
+
+#[test]
+fn test_lz_compressor() {
+    let lz = LzCompressor;
+    let mut json = vec![];
+    for i in 0..200 {
+        json.extend_from_slice(
+            format!(r#"{{"id":{i},"name":"user{i}","active":true}}"#)
+                .as_bytes(),
+        );
+    }
+    let inputs: [&[u8]; 6] = [
+        b"",
+        b"a",
+        b"abcabcabcabcabcabc",
+        &[7; 1000],
+        &json,
+        b"no repeats",
+    ];
+    for input in inputs {
+        let compressed = lz.compress(input);
+        assert_eq!(lz.decompress(&compressed).as_deref(), Some(input));
+    }
+    assert!(lz.compress(&json).len() < json.len() / 3);
+
+    let compressed = lz.compress(&json);
+    assert_eq!(lz.decompress(&compressed[..compressed.len() - 1]), None);
+    // a copy from before the start
+    assert_eq!(lz.decompress(&[4, 0x80, 1]), None);
+    // more output than the length says
+    assert_eq!(lz.decompress(&[1, 1, b'a', b'b']), None);
+}
@@ -0,0 +1,43 @@
+// CRC-32C (Castagnoli), reflected polynomial
+const POLY: u32 = 0x82f6_3b78;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// continues a crc previously returned by value() or extend()
+pub fn extend(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc = TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+pub fn value(data: &[u8]) -> u32 {
+    extend(0, data)
+}
+
+#[test]
+fn test_crc32c() {
+    assert_eq!(value(b""), 0);
+    assert_eq!(value(b"123456789"), 0xe306_9283);
+    assert_eq!(value(&[0; 32]), 0x8a91_36aa);
+    assert_eq!(extend(value(b"1234"), b"56789"), value(b"123456789"));
+}
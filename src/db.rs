@@ -0,0 +1,2229 @@
+use crate::cache::CacheStats;
+use crate::comparator::Comparator;
+use crate::db_iter::{DBIterator, Entries};
+use crate::dbformat::{
+    internal_key, parse_internal_key, user_key, InternalFilterPolicy,
+    InternalKeyComparator, RecordKind, KIND_FOR_SEEK, MAX_SEQUENCE,
+};
+use crate::filename::{self, file_name, parse_file_name, sync_dir, FileType};
+use crate::iterator::{self, Entry, Source};
+use crate::log;
+use crate::memtable::MemTable;
+use crate::merge::MergingIterator;
+use crate::range_del::{FragmentedTombstones, RangeTombstone};
+use crate::snapshot::{Snapshot, SnapshotList};
+use crate::table::TableBuilder;
+use crate::table_cache::TableCache;
+use crate::version::{Compaction, VersionSet, NUM_LEVELS};
+use crate::version_edit::{FileMetaData, VersionEdit};
+use crate::write_batch::{BatchRecord, WriteBatch};
+use crate::{Error, Options, ReadOptions, WriteOptions, KV};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+// a background error is handed to every write after it
+fn duplicate_error(e: &Error) -> Error {
+    match e {
+        Error::KeyNotFound => Error::KeyNotFound,
+        Error::Corruption { offset, reason } => Error::Corruption {
+            offset: *offset,
+            reason: reason.clone(),
+        },
+        Error::InvalidArgument(reason) => {
+            Error::InvalidArgument(reason.clone())
+        }
+        Error::Locked => Error::Locked,
+        Error::Io(e) => Error::Io(io::Error::new(e.kind(), e.to_string())),
+    }
+}
+
+// adds the records of batch to mem, numbered from the batch's
+// sequence number on
+fn insert_batch(mem: &MemTable, batch: &WriteBatch) {
+    for (i, record) in batch.iter().enumerate() {
+        let sequence = batch.sequence() + i as u64;
+        match record {
+            BatchRecord::Put(key, value) => {
+                mem.add(sequence, RecordKind::Value, key, value)
+            }
+            BatchRecord::Delete(key) => {
+                mem.add(sequence, RecordKind::Deletion, key, b"")
+            }
+            BatchRecord::DeleteRange(start, end) => {
+                mem.add_range_deletion(sequence, start, end)
+            }
+        }
+    }
+}
+
+// a write waiting its turn, None just makes room in the memtable,
+// forcing it out
+struct PendingWrite {
+    id: u64,
+    batch: Option<WriteBatch>,
+    sync: bool,
+    // signalled once it is done or first in line
+    cv: Arc<Condvar>,
+}
+
+// how far a compact() has got: the level being pushed down and
+// the tables it had when the push got to it, None before then, so
+// tables added by writes after it started don't keep it going
+struct ManualCompaction {
+    level: usize,
+    files: Option<HashSet<u64>>,
+}
+
+// the most a group commit appends at once, less if the first write
+// is small so it doesn't wait long on the ones it takes along
+const MAX_GROUP_SIZE: usize = 1 << 20;
+const SMALL_WRITE_SIZE: usize = 128 << 10;
+
+struct State {
+    // written to by the first of writers without holding the state
+    // lock, only ever switched out by it too
+    log: Arc<Mutex<log::Writer>>,
+    log_number: u64,
+    // some record of log has yet to be synced
+    log_dirty: bool,
+    mem: Arc<MemTable>,
+    // a full memtable waiting to be written out to level 0,
+    // its log is the one before log_number
+    imm: Option<Arc<MemTable>>,
+    versions: VersionSet,
+    // set by compact() until every level has been pushed down
+    manual_compaction: Option<ManualCompaction>,
+    // the first one is writing, the rest take turns after it or are
+    // taken along in its group
+    writers: VecDeque<PendingWrite>,
+    // of the writes taken along by another, for them to pick up
+    write_results: HashMap<u64, Result<(), Error>>,
+    next_writer_id: u64,
+    // the background thread stops working after an error
+    bg_error: Option<Error>,
+    shutting_down: bool,
+}
+
+// the part of the database shared with the background thread
+struct Inner {
+    dir: PathBuf,
+    // the LOCK file, locked until it is closed
+    _lock: File,
+    // as given, but with the filter policy wrapped to work on
+    // internal keys
+    options: Options,
+    comparator: Arc<InternalKeyComparator>,
+    table_cache: Arc<TableCache>,
+    snapshots: Arc<SnapshotList>,
+    state: Mutex<State>,
+    // signalled whenever there is background work to do
+    // or some of it got done
+    bg_cv: Condvar,
+}
+
+// what the background thread does next
+enum Work {
+    // writes imm out to level 0
+    Flush(Arc<MemTable>),
+    // along with the sequence number of the oldest snapshot in use,
+    // or of the last write when there is none
+    Compact(Compaction, u64),
+}
+
+// a directory holding write-ahead logs, tables and a manifest,
+// each file named after a number that only ever goes up
+//
+// writes are appended to the log and applied to the memtable, each
+// record tagged with a sequence number one past the last, a full
+// memtable is written out to a level 0 table by a background thread,
+// which also merges tables down the levels so reads have only a few
+// of them to look at, reads go through the memtables and then the
+// tables, newest first
+//
+// which tables make up the database is only ever changed through
+// the manifest, so after a crash it reopens to exactly the files
+// the last change committed to
+//
+// older versions of a key are kept around until no snapshot can
+// see them anymore, compactions drop the rest
+//
+// it can be shared between threads, writes queue up and the first
+// in line commits the ones behind it along with its own, reads only
+// take the lock long enough to pick up the memtables and tables
+// to read from
+pub struct Database {
+    inner: Arc<Inner>,
+    bg_thread: Option<thread::JoinHandle<()>>,
+    // syncs the log every sync_interval, if there is one
+    sync_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Database {
+    pub fn new(path: &str, truncate: bool) -> Result<Self, Error> {
+        Self::with_options(path, truncate, Options::default())
+    }
+
+    // creates the database if it is missing, truncate empties it first
+    pub fn with_options(
+        path: &str,
+        truncate: bool,
+        options: Options,
+    ) -> Result<Self, Error> {
+        Self::open_dir(path, &options.create_if_missing(true), truncate)
+    }
+
+    // opens the database at path, whether it may or must be created
+    // is up to options, which are checked first
+    //
+    // it stays locked for as long as it is open, Error::Locked if
+    // it already is
+    pub fn open(path: &str, options: &Options) -> Result<Self, Error> {
+        Self::open_dir(path, options, false)
+    }
+
+    fn open_dir(
+        path: &str,
+        options: &Options,
+        truncate: bool,
+    ) -> Result<Self, Error> {
+        options.validate()?;
+        let dir = PathBuf::from(path);
+        // checked before taking the lock, which would leave a LOCK file
+        // behind in a database that was never there
+        let exists = !truncate
+            && dir.is_dir()
+            && filename::read_current_file(&dir)?.is_some();
+        if !exists && !options.create_if_missing {
+            return Err(Error::InvalidArgument(format!(
+                "{path} does not exist (create_if_missing is false)"
+            )));
+        }
+        if exists && options.error_if_exists {
+            return Err(Error::InvalidArgument(format!(
+                "{path} exists (error_if_exists is true)"
+            )));
+        }
+        fs::create_dir_all(&dir)?;
+        // nothing is touched before the lock is taken
+        let lock = filename::lock_dir(&dir)?;
+        if truncate {
+            for (number, file_type) in list_files(&dir)? {
+                if file_type != FileType::Lock {
+                    fs::remove_file(file_name(&dir, number, file_type))?;
+                }
+            }
+        }
+
+        let options = options.clone();
+        let options = Options {
+            filter_policy: options.filter_policy.map(|policy| {
+                let extractor = options.prefix_extractor.clone();
+                Arc::new(InternalFilterPolicy::new(policy, extractor)) as Arc<_>
+            }),
+            ..options
+        };
+        let comparator =
+            Arc::new(InternalKeyComparator::new(options.comparator.clone()));
+        let mut versions =
+            VersionSet::new(dir.clone(), options.clone(), comparator.clone());
+        versions.recover()?;
+        // logs older than the manifest's log number are already in
+        // tables, every table it lists must be there
+        let mut logs = vec![];
+        let mut tables = vec![];
+        for (number, file_type) in list_files(&dir)? {
+            versions.mark_file_number_used(number);
+            match file_type {
+                FileType::Log if number >= versions.log_number() => {
+                    logs.push(number)
+                }
+                FileType::Table => tables.push(number),
+                _ => {}
+            }
+        }
+        logs.sort_unstable();
+        let current = versions.current();
+        if let Some(file) = current
+            .files
+            .iter()
+            .flatten()
+            .find(|file| !tables.contains(&file.number))
+        {
+            return Err(Error::Corruption {
+                offset: None,
+                reason: format!("missing table {:06}", file.number),
+            });
+        }
+
+        let table_cache = Arc::new(TableCache::new(
+            dir.clone(),
+            options.clone(),
+            comparator.clone(),
+        ));
+        if options.paranoid_checks {
+            for file in current.files.iter().flatten() {
+                table_cache.get(file)?;
+            }
+        }
+
+        let mem = Arc::new(MemTable::new(comparator.clone()));
+        let mut last_sequence = versions.last_sequence();
+        for number in &logs {
+            let path = file_name(&dir, *number, FileType::Log);
+            let complete = log::replay(&path, |record| {
+                // a batch is applied whole or, if its record didn't
+                // make it, not at all
+                let batch = WriteBatch::from_contents(record)?;
+                insert_batch(&mem, &batch);
+                let last = batch.sequence() + batch.len() as u64;
+                last_sequence = last_sequence.max(last.saturating_sub(1));
+                Ok(())
+            })?;
+            // only the last log can have been cut short by a crash,
+            // the writes in later logs came after the ones lost here
+            if !complete && Some(number) != logs.last() {
+                return Err(Error::Corruption {
+                    offset: None,
+                    reason: format!("log {number:06} cut short"),
+                });
+            }
+        }
+        versions.set_last_sequence(last_sequence);
+
+        let log_number = versions.new_file_number();
+        let log =
+            log::Writer::create(&file_name(&dir, log_number, FileType::Log))?;
+        let inner = Arc::new(Inner {
+            _lock: lock,
+            table_cache,
+            snapshots: Arc::new(SnapshotList::new()),
+            dir,
+            options,
+            state: Mutex::new(State {
+                log: Arc::new(Mutex::new(log)),
+                log_number,
+                log_dirty: false,
+                mem: Arc::new(MemTable::new(comparator.clone())),
+                imm: None,
+                versions,
+                manual_compaction: None,
+                writers: VecDeque::new(),
+                write_results: HashMap::new(),
+                next_writer_id: 0,
+                bg_error: None,
+                shutting_down: false,
+            }),
+            bg_cv: Condvar::new(),
+            comparator,
+        });
+
+        // replayed writes go straight to a table,
+        // after that the old logs are no longer needed
+        let mut edit = VersionEdit {
+            log_number: Some(log_number),
+            ..VersionEdit::default()
+        };
+        if !mem.is_empty() {
+            for file in inner.write_tables(
+                iterator::entries(mem.iter()),
+                mem.range_tombstones(),
+                false,
+            )? {
+                edit.added.push((0, file));
+            }
+        }
+        inner.install(&mut inner.state.lock().unwrap(), &mut edit)?;
+
+        let bg_inner = inner.clone();
+        let bg_thread = thread::spawn(move || bg_inner.background_loop());
+        let sync_thread = inner.options.sync_interval.map(|_| {
+            let sync_inner = inner.clone();
+            thread::spawn(move || sync_inner.sync_loop())
+        });
+        Ok(Self {
+            inner,
+            bg_thread: Some(bg_thread),
+            sync_thread,
+        })
+    }
+
+    // applies every update of batch, or none of them if a crash
+    // gets in the way, as a single log record
+    pub fn write(&self, batch: &WriteBatch) -> Result<(), Error> {
+        self.write_with_options(batch, &WriteOptions::default())
+    }
+
+    // same, synced first if options say so, which makes every
+    // write before it durable too
+    pub fn write_with_options(
+        &self,
+        batch: &WriteBatch,
+        options: &WriteOptions,
+    ) -> Result<(), Error> {
+        self.inner.write(Some(batch.clone()), options.sync)
+    }
+
+    // deletes every key in [start, end) with a single record, the
+    // keys it covers only go away for good as compactions get to them
+    pub fn delete_range(&self, start: &[u8], end: &[u8]) -> Result<(), Error> {
+        if self.inner.options.comparator.compare(start, end)
+            == Ordering::Greater
+        {
+            return Err(Error::InvalidArgument(
+                "range deletion ends before it starts".to_string(),
+            ));
+        }
+        let mut batch = WriteBatch::new();
+        batch.delete_range(start, end);
+        self.write(&batch)
+    }
+
+    // a handle on the database as it is right now, reads and
+    // iterators given it don't see any write made after
+    pub fn snapshot(&self) -> Snapshot {
+        let state = self.inner.state.lock().unwrap();
+        self.inner.snapshots.acquire(state.versions.last_sequence())
+    }
+
+    pub fn get_at(
+        &self,
+        key: &[u8],
+        snapshot: &Snapshot,
+    ) -> Result<Vec<u8>, Error> {
+        let options = ReadOptions::default().snapshot(Some(snapshot));
+        self.get_with_options(key, &options)
+    }
+
+    pub fn get_with_options(
+        &self,
+        key: &[u8],
+        options: &ReadOptions,
+    ) -> Result<Vec<u8>, Error> {
+        self.lookup(key, options)?.ok_or(Error::KeyNotFound)
+    }
+
+    // a cursor over the entries in key order, it sees the database
+    // as it was when it was made
+    pub fn iter(&self) -> DBIterator {
+        self.iter_with_options(&ReadOptions::default())
+    }
+
+    // same, as of snapshot
+    pub fn iter_at(&self, snapshot: &Snapshot) -> DBIterator {
+        self.iter_with_options(&ReadOptions::default().snapshot(Some(snapshot)))
+    }
+
+    pub fn iter_with_options(&self, options: &ReadOptions) -> DBIterator {
+        self.inner.iter(options, None)
+    }
+
+    // the live entries with keys starting with prefix, in order,
+    // from the first one on
+    //
+    // only under a bytewise comparator, any other may scatter the
+    // keys with a prefix all over
+    pub fn scan_prefix(&self, prefix: &[u8]) -> Result<Entries, Error> {
+        self.scan_prefix_with_options(prefix, &ReadOptions::default())
+    }
+
+    pub fn scan_prefix_with_options(
+        &self,
+        prefix: &[u8],
+        options: &ReadOptions,
+    ) -> Result<Entries, Error> {
+        if !self.inner.options.comparator.is_bytewise() {
+            return Err(Error::InvalidArgument(
+                "prefix scans need a bytewise comparator".to_string(),
+            ));
+        }
+        Ok(self
+            .inner
+            .iter(options, Some(prefix))
+            .prefix(prefix)
+            .entries())
+    }
+
+    // hits and misses of the block cache, all zero without one
+    pub fn block_cache_stats(&self) -> CacheStats {
+        self.inner.table_cache.block_cache_stats()
+    }
+
+    // writes out the memtable and pushes every table down to the
+    // last level, dropping overwritten entries and tombstones
+    // no snapshot needs, tables written while it runs may be left
+    // where they are
+    pub fn compact(&self) -> Result<(), Error> {
+        let inner = &self.inner;
+        inner.write(None, false)?;
+        let mut state = inner.state.lock().unwrap();
+        state.manual_compaction = Some(ManualCompaction {
+            level: 0,
+            files: None,
+        });
+        inner.bg_cv.notify_all();
+        while state.manual_compaction.is_some() && state.bg_error.is_none() {
+            state = inner.bg_cv.wait(state).unwrap();
+        }
+        match &state.bg_error {
+            Some(e) => Err(duplicate_error(e)),
+            None => Ok(()),
+        }
+    }
+
+    // the latest value of key, or the one as of the snapshot
+    // of options
+    fn lookup(
+        &self,
+        key: &[u8],
+        options: &ReadOptions,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let (mem, imm, version, sequence) = {
+            let state = self.inner.state.lock().unwrap();
+            let sequence = options
+                .snapshot
+                .map_or(state.versions.last_sequence(), Snapshot::sequence);
+            let version = state.versions.current();
+            (state.mem.clone(), state.imm.clone(), version, sequence)
+        };
+        if let Some(value) = mem.get(key, sequence) {
+            return Ok(value);
+        }
+        if let Some(value) = imm.and_then(|imm| imm.get(key, sequence)) {
+            return Ok(value);
+        }
+        let table_cache = &self.inner.table_cache;
+        let value = version.get(key, sequence, table_cache, options)?;
+        Ok(value.flatten())
+    }
+
+    // waits until the background thread has nothing left to do
+    #[cfg(test)]
+    fn wait_for_background(&self) {
+        let inner = &self.inner;
+        let mut state = inner.state.lock().unwrap();
+        while state.bg_error.is_none()
+            && (state.imm.is_some() || state.versions.needs_compaction())
+        {
+            state = inner.bg_cv.wait(state).unwrap();
+        }
+    }
+}
+
+impl Drop for Database {
+    // the background thread finishes what it is doing and stops,
+    // a memtable it didn't get to is still in its log
+    fn drop(&mut self) {
+        self.inner.state.lock().unwrap().shutting_down = true;
+        self.inner.bg_cv.notify_all();
+        let threads = [self.bg_thread.take(), self.sync_thread.take()];
+        for thread in threads.into_iter().flatten() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// the files of the database in dir
+fn list_files(dir: &Path) -> Result<Vec<(u64, FileType)>, Error> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if let Some(file) = name.to_str().and_then(parse_file_name) {
+            files.push(file);
+        }
+    }
+    Ok(files)
+}
+
+impl Inner {
+    fn new_file_number(&self) -> u64 {
+        self.state.lock().unwrap().versions.new_file_number()
+    }
+
+    // queues up the write and waits for it to be done, either by
+    // a writer ahead of it or, once it is first in line, by itself
+    //
+    // the first in line takes the writes queued behind it along,
+    // appending them to the log as a single record, synced once, then
+    // wakes each of them with how it went
+    fn write(
+        &self,
+        batch: Option<WriteBatch>,
+        sync: bool,
+    ) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_writer_id;
+        state.next_writer_id += 1;
+        let cv = Arc::new(Condvar::new());
+        state.writers.push_back(PendingWrite {
+            id,
+            batch,
+            sync,
+            cv: cv.clone(),
+        });
+        loop {
+            if let Some(result) = state.write_results.remove(&id) {
+                return result;
+            }
+            if state.writers[0].id == id {
+                break;
+            }
+            state = cv.wait(state).unwrap();
+        }
+
+        let force = state.writers[0].batch.is_none();
+        let (mut state, result, taken) = match self.make_room(state, force) {
+            Ok(state) => self.write_group(state),
+            Err(e) => (self.state.lock().unwrap(), Err(e), 1),
+        };
+        state.writers.pop_front();
+        for _ in 1..taken {
+            let writer = state.writers.pop_front().unwrap();
+            let follower_result = match &result {
+                Ok(()) => Ok(()),
+                Err(e) => Err(duplicate_error(e)),
+            };
+            state.write_results.insert(writer.id, follower_result);
+            writer.cv.notify_one();
+        }
+        if let Some(next) = state.writers.front() {
+            next.cv.notify_one();
+        }
+        result
+    }
+
+    // writes the first of writers along with the ones queued behind it
+    // that fit, returns how it went and how many it took
+    fn write_group<'a>(
+        &'a self,
+        state: MutexGuard<'a, State>,
+    ) -> (MutexGuard<'a, State>, Result<(), Error>, usize) {
+        let leader = &state.writers[0];
+        let Some(mut group) = leader.batch.clone() else {
+            return (state, Ok(()), 1);
+        };
+        let sync = leader.sync;
+        let size = group.contents().len();
+        let max_size = if size <= SMALL_WRITE_SIZE {
+            size + SMALL_WRITE_SIZE
+        } else {
+            MAX_GROUP_SIZE
+        };
+        let mut taken = 1;
+        for writer in state.writers.iter().skip(1) {
+            // a synced write can't be made by a leader that doesn't sync,
+            // and one making room goes on its own
+            let Some(batch) = &writer.batch else { break };
+            if writer.sync && !sync
+                || group.contents().len() + batch.contents().len() > max_size
+            {
+                break;
+            }
+            group.append(batch);
+            taken += 1;
+        }
+        group.set_sequence(state.versions.last_sequence() + 1);
+        let log = state.log.clone();
+        let mem = state.mem.clone();
+
+        // the others wait their turn, readers don't see any of it
+        // until last_sequence says so
+        drop(state);
+        let result = (|| {
+            let mut log = log.lock().unwrap();
+            log.add_record(group.contents())?;
+            if sync {
+                log.sync()?;
+            }
+            Ok(())
+        })();
+        if result.is_ok() {
+            insert_batch(&mem, &group);
+        }
+        let mut state = self.state.lock().unwrap();
+        match &result {
+            Ok(()) => {
+                let last_sequence =
+                    state.versions.last_sequence() + group.len() as u64;
+                state.versions.set_last_sequence(last_sequence);
+                state.log_dirty = !sync;
+            }
+            // there's no telling how much of the record made it
+            // to the log, so every write after fails too
+            Err(e) => {
+                state.bg_error.get_or_insert(duplicate_error(e));
+                self.bg_cv.notify_all();
+            }
+        }
+        (state, result, taken)
+    }
+
+    // waits until the memtable has room for a write, or just until it
+    // can be switched out when force is set, switching it out for a
+    // fresh one and a fresh log if it is full
+    fn make_room<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, State>,
+        force: bool,
+    ) -> Result<MutexGuard<'a, State>, Error> {
+        loop {
+            if let Some(e) = &state.bg_error {
+                return Err(duplicate_error(e));
+            }
+            if state.mem.is_empty()
+                || !force
+                    && state.mem.approximate_size()
+                        < self.options.write_buffer_size
+            {
+                return Ok(state);
+            }
+            if state.imm.is_some()
+                || state.versions.current().files[0].len()
+                    >= self.options.level0_stop_writes_trigger
+            {
+                state = self.bg_cv.wait(state).unwrap();
+                continue;
+            }
+            // the periodic sync only ever gets to the current log
+            if state.log_dirty && self.options.sync_interval.is_some() {
+                state.log.lock().unwrap().sync()?;
+                state.log_dirty = false;
+            }
+            let log_number = state.versions.new_file_number();
+            let log = log::Writer::create(&file_name(
+                &self.dir,
+                log_number,
+                FileType::Log,
+            ))?;
+            state.log = Arc::new(Mutex::new(log));
+            state.log_number = log_number;
+            let mem = Arc::new(MemTable::new(self.comparator.clone()));
+            state.imm = Some(std::mem::replace(&mut state.mem, mem));
+            self.bg_cv.notify_all();
+        }
+    }
+
+    fn background_loop(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !state.shutting_down {
+            let work = match state.bg_error {
+                Some(_) => None,
+                None => self.pick_work(&mut state),
+            };
+            let Some(work) = work else {
+                state = self.bg_cv.wait(state).unwrap();
+                continue;
+            };
+            // the merging is done without holding the lock,
+            // nothing else changes the version meanwhile
+            drop(state);
+            let result = self.run(&work);
+            state = self.state.lock().unwrap();
+            let result = result.and_then(|mut edit| {
+                if let Work::Flush(_) = work {
+                    // the memtable's log goes along with it
+                    edit.log_number = Some(state.log_number);
+                }
+                self.install(&mut state, &mut edit)?;
+                if let Work::Flush(_) = work {
+                    state.imm = None;
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                state.bg_error = Some(e);
+            }
+            self.bg_cv.notify_all();
+        }
+    }
+
+    // syncs the log whenever sync_interval has passed since the last
+    // time with some record added meanwhile, the writes go on while
+    // it syncs
+    fn sync_loop(&self) {
+        let interval = self.options.sync_interval.unwrap();
+        let mut next = Instant::now() + interval;
+        let mut state = self.state.lock().unwrap();
+        while !state.shutting_down {
+            let now = Instant::now();
+            if now < next {
+                state = self.bg_cv.wait_timeout(state, next - now).unwrap().0;
+                continue;
+            }
+            next = now + interval;
+            if !state.log_dirty {
+                continue;
+            }
+            let log = state.log.clone();
+            state.log_dirty = false;
+            drop(state);
+            // holds up the writes only as long as it takes to get
+            // a handle on the file
+            let file = log.lock().unwrap().file();
+            let result = file.and_then(|file| Ok(file.sync_all()?));
+            state = self.state.lock().unwrap();
+            if let Err(e) = result {
+                state.bg_error.get_or_insert(e);
+                self.bg_cv.notify_all();
+            }
+        }
+        // whatever is left gets synced on the way out
+        if state.log_dirty {
+            let _ = state.log.lock().unwrap().sync();
+        }
+    }
+
+    // a full memtable comes first since writes may be waiting on it
+    fn pick_work(&self, state: &mut State) -> Option<Work> {
+        if let Some(mem) = &state.imm {
+            return Some(Work::Flush(mem.clone()));
+        }
+        let smallest_snapshot = self
+            .snapshots
+            .oldest()
+            .unwrap_or(state.versions.last_sequence());
+        if let Some(manual) = &mut state.manual_compaction {
+            let version = state.versions.current();
+            while manual.level < NUM_LEVELS - 1 {
+                let files = manual.files.get_or_insert_with(|| {
+                    let files = &version.files[manual.level];
+                    files.iter().map(|file| file.number).collect()
+                });
+                if let Some(compaction) =
+                    version.manual_compaction(manual.level, files)
+                {
+                    return Some(Work::Compact(compaction, smallest_snapshot));
+                }
+                manual.level += 1;
+                manual.files = None;
+            }
+            state.manual_compaction = None;
+            self.bg_cv.notify_all();
+        }
+        let compaction = state.versions.pick_compaction()?;
+        Some(Work::Compact(compaction, smallest_snapshot))
+    }
+
+    fn run(&self, work: &Work) -> Result<VersionEdit, Error> {
+        match work {
+            Work::Flush(mem) => {
+                let mut edit = VersionEdit::default();
+                let entries = iterator::entries(mem.iter());
+                let range_tombstones = mem.range_tombstones();
+                for file in
+                    self.write_tables(entries, range_tombstones, false)?
+                {
+                    edit.added.push((0, file));
+                }
+                Ok(edit)
+            }
+            Work::Compact(compaction, smallest_snapshot) => {
+                self.compact(compaction, *smallest_snapshot)
+            }
+        }
+    }
+
+    fn compact(
+        &self,
+        compaction: &Compaction,
+        smallest_snapshot: u64,
+    ) -> Result<VersionEdit, Error> {
+        let level = compaction.level;
+        let mut edit = compaction.edit();
+        if compaction.is_trivial_move() {
+            edit.added
+                .push((level + 1, compaction.inputs[0][0].clone()));
+            return Ok(edit);
+        }
+        let version = &compaction.version;
+        // the inputs are about to go away, no use caching them
+        let options = ReadOptions::default().fill_cache(false);
+        let level_iter = |files: &[Arc<FileMetaData>]| -> Source {
+            Box::new(version.level_iter(
+                &self.table_cache,
+                &options,
+                files.to_vec(),
+            ))
+        };
+        let mut sources = vec![];
+        if level == 0 {
+            for file in &compaction.inputs[0] {
+                sources.push(level_iter(std::slice::from_ref(file)));
+            }
+        } else {
+            sources.push(level_iter(&compaction.inputs[0]));
+        }
+        sources.push(level_iter(&compaction.inputs[1]));
+        // a version only needs to stay while some snapshot may see it,
+        // which no snapshot does once a newer version or a range
+        // deletion of it is older than all of them, a tombstone only
+        // needs to stay while something further down may still hold
+        // the key, or any key of its range
+        let user_comparator = self.comparator.user_comparator();
+        let inputs = compaction.inputs.iter().flatten();
+        let range_tombstones = inputs
+            .flat_map(|file| &file.range_tombstones)
+            .cloned()
+            .collect::<Vec<_>>();
+        let fragmented = FragmentedTombstones::new(
+            user_comparator.as_ref(),
+            &range_tombstones,
+        );
+        let mut current_key: Option<Vec<u8>> = None;
+        let mut last_sequence_for_key = u64::MAX;
+        let merged = MergingIterator::new(self.comparator.clone(), sources);
+        let live = iterator::entries(merged).filter(|entry| {
+            // errors go through, unparsable keys are left as they are
+            let Some((key, sequence, kind)) = entry
+                .as_ref()
+                .ok()
+                .and_then(|(key, _)| parse_internal_key(key))
+            else {
+                return true;
+            };
+            if current_key.as_ref().is_none_or(|current| {
+                user_comparator.compare(current, key) != Ordering::Equal
+            }) {
+                current_key = Some(key.to_vec());
+                last_sequence_for_key = u64::MAX;
+            }
+            let hidden = last_sequence_for_key <= smallest_snapshot
+                || fragmented
+                    .newest_covering(
+                        user_comparator.as_ref(),
+                        key,
+                        smallest_snapshot,
+                    )
+                    .is_some_and(|covering| covering > sequence);
+            last_sequence_for_key = sequence;
+            let obsolete = kind == RecordKind::Deletion
+                && sequence <= smallest_snapshot
+                && version.is_base_level_for_key(level + 1, key);
+            !hidden && !obsolete
+        });
+        let range_tombstones = range_tombstones
+            .iter()
+            .filter(|tombstone| {
+                tombstone.sequence > smallest_snapshot
+                    || !version.is_base_level_for_range(
+                        level + 1,
+                        &tombstone.start,
+                        &tombstone.end,
+                    )
+            })
+            .cloned()
+            .collect();
+        for file in self.write_tables(live, range_tombstones, true)? {
+            edit.added.push((level + 1, file));
+        }
+        Ok(edit)
+    }
+
+    // writes sorted entries out to new tables, starting a new one
+    // whenever one grows past max_file_size if split is set, the
+    // versions of a user key always go to the same table
+    //
+    // the range tombstones are cut up between the tables at the first
+    // key of each, a table's key range takes in the ones it gets
+    fn write_tables(
+        &self,
+        entries: impl Iterator<Item = Result<Entry, Error>>,
+        range_tombstones: Vec<RangeTombstone>,
+        split: bool,
+    ) -> Result<Vec<Arc<FileMetaData>>, Error> {
+        let mut files = vec![];
+        let mut current: Option<(TableBuilder, FileMetaData)> = None;
+        for entry in entries {
+            let (key, value) = entry?;
+            if let Some((builder, file)) = &current {
+                let user_comparator = self.comparator.user_comparator();
+                if split
+                    && builder.file_size() >= self.options.max_file_size
+                    && user_comparator
+                        .compare(user_key(&key), user_key(&file.largest))
+                        != Ordering::Equal
+                {
+                    let (builder, file) = current.take().unwrap();
+                    files.push(self.finish_table(builder, file)?);
+                }
+            }
+            let (builder, file) = match &mut current {
+                Some(current) => current,
+                None => current.insert(self.new_table(key.clone())?),
+            };
+            builder.add(&key, &value)?;
+            file.largest = key;
+        }
+        if let Some((builder, file)) = current {
+            files.push(self.finish_table(builder, file)?);
+        }
+        if files.is_empty() && !range_tombstones.is_empty() {
+            // an empty table to carry them
+            let (builder, file) = self.new_table(vec![])?;
+            files.push(self.finish_table(builder, file)?);
+        }
+
+        let user_comparator = self.comparator.user_comparator().as_ref();
+        let cuts = files
+            .iter()
+            .skip(1)
+            .map(|file| user_key(&file.smallest).to_vec())
+            .collect::<Vec<_>>();
+        for (i, file) in files.iter_mut().enumerate() {
+            let lower = i.checked_sub(1).map(|i| cuts[i].as_slice());
+            let upper = cuts.get(i).map(Vec::as_slice);
+            for tombstone in &range_tombstones {
+                let Some(tombstone) =
+                    tombstone.clip(user_comparator, lower, upper)
+                else {
+                    continue;
+                };
+                // before every version of start, and since the end is
+                // left out, of end too
+                let start =
+                    internal_key(&tombstone.start, MAX_SEQUENCE, KIND_FOR_SEEK);
+                let end =
+                    internal_key(&tombstone.end, MAX_SEQUENCE, KIND_FOR_SEEK);
+                if file.smallest.is_empty()
+                    || self.comparator.compare(&start, &file.smallest)
+                        == Ordering::Less
+                {
+                    file.smallest = start;
+                }
+                if file.largest.is_empty()
+                    || self.comparator.compare(&end, &file.largest)
+                        == Ordering::Greater
+                {
+                    file.largest = end;
+                }
+                file.range_tombstones.push(tombstone);
+            }
+        }
+        Ok(files.into_iter().map(Arc::new).collect())
+    }
+
+    fn new_table(
+        &self,
+        smallest: Vec<u8>,
+    ) -> Result<(TableBuilder, FileMetaData), Error> {
+        let number = self.new_file_number();
+        // a table only shows up under its real name once complete
+        let tmp_path = file_name(&self.dir, number, FileType::Temp);
+        let builder = TableBuilder::new(
+            File::create(tmp_path)?,
+            &self.options,
+            self.comparator.clone(),
+        );
+        let file = FileMetaData {
+            number,
+            size: 0,
+            smallest,
+            largest: vec![],
+            range_tombstones: vec![],
+        };
+        Ok((builder, file))
+    }
+
+    fn finish_table(
+        &self,
+        builder: TableBuilder,
+        mut file: FileMetaData,
+    ) -> Result<FileMetaData, Error> {
+        file.size = builder.finish()?;
+        fs::rename(
+            file_name(&self.dir, file.number, FileType::Temp),
+            file_name(&self.dir, file.number, FileType::Table),
+        )?;
+        sync_dir(&self.dir)?;
+        Ok(file)
+    }
+
+    // commits edit to the manifest, then removes whatever it
+    // left behind
+    fn install(
+        &self,
+        state: &mut State,
+        edit: &mut VersionEdit,
+    ) -> Result<(), Error> {
+        state.versions.log_and_apply(edit)?;
+        self.remove_obsolete_files(state)
+    }
+
+    // old logs and manifests, tables no version in use refers to and
+    // temporary files, only the background thread writes tables so
+    // none of those are still being written when it calls this
+    fn remove_obsolete_files(&self, state: &mut State) -> Result<(), Error> {
+        let live = state.versions.live_files();
+        for (number, file_type) in list_files(&self.dir)? {
+            let keep = match file_type {
+                FileType::Log => number >= state.versions.log_number(),
+                FileType::Table => {
+                    live.contains(&number) || state.versions.torn_manifest()
+                }
+                FileType::Manifest => {
+                    number >= state.versions.manifest_number()
+                }
+                FileType::Current | FileType::Lock => true,
+                FileType::Temp => false,
+            };
+            if !keep {
+                if file_type == FileType::Table {
+                    self.table_cache.evict(number);
+                }
+                fs::remove_file(file_name(&self.dir, number, file_type))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KV for Database {
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>, Error> {
+        self.get_with_options(key, &ReadOptions::default())
+    }
+    fn has(&self, key: &[u8]) -> Result<bool, Error> {
+        Ok(self.lookup(key, &ReadOptions::default())?.is_some())
+    }
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let mut batch = WriteBatch::new();
+        batch.put(key, value);
+        self.write(&batch)
+    }
+    fn delete(&self, key: &[u8]) -> Result<(), Error> {
+        let mut batch = WriteBatch::new();
+        batch.delete(key);
+        self.write(&batch)
+    }
+}
+
+#[test]
+fn test_full() {
+    let db = Database::new("/tmp/test_db_full", true).unwrap();
+
+    assert!(!db.has(b"abc").unwrap());
+
+    db.put(b"abc", b"xyz").unwrap();
+
+    assert!(db.has(b"abc").unwrap());
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+
+    db.delete(b"abc").unwrap();
+
+    assert!(!db.has(b"abc").unwrap());
+}
+
+#[test]
+fn test_reopen() {
+    let db = Database::new("/tmp/test_db_reopen", true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    db.put(b"abc", b"123").unwrap();
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_reopen", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"123");
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+
+    db.put(b"ghi", b"rst").unwrap();
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_reopen", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"123");
+    assert_eq!(db.get(b"ghi").unwrap(), b"rst");
+}
+
+#[test]
+fn test_delete_persists() {
+    let db = Database::new("/tmp/test_db_delete_persists", true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    db.delete(b"abc").unwrap();
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_delete_persists", false).unwrap();
+    assert!(!db.has(b"abc").unwrap());
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+
+    // a put after the tombstone brings the key back
+    db.put(b"abc", b"123").unwrap();
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_delete_persists", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"123");
+}
+
+#[test]
+fn test_bad_header() {
+    fs::create_dir_all("/tmp/test_db_bad_header").unwrap();
+    fs::write("/tmp/test_db_bad_header/000001.log", b"abc,xyz\n").unwrap();
+    assert!(matches!(
+        Database::new("/tmp/test_db_bad_header", false),
+        Err(Error::Corruption {
+            offset: Some(0),
+            ..
+        })
+    ));
+}
+
+#[cfg(test)]
+fn files(dir: &str, file_type: FileType) -> Vec<PathBuf> {
+    let mut files = fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| {
+            let name = entry.unwrap().file_name();
+            let (number, t) = parse_file_name(name.to_str()?)?;
+            (t == file_type).then(|| file_name(Path::new(dir), number, t))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_corruption() {
+    let db = Database::new("/tmp/test_db_corruption", true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    db.compact().unwrap();
+    db.put(b"ghi", b"rst").unwrap();
+
+    // flip the first byte of the table behind the database's back
+    let table = &files("/tmp/test_db_corruption", FileType::Table)[0];
+    let mut bytes = fs::read(table).unwrap();
+    bytes[0] ^= 0xff;
+    fs::write(table, &bytes).unwrap();
+
+    assert_eq!(db.get(b"ghi").unwrap(), b"rst");
+    assert!(matches!(
+        db.get(b"def"),
+        Err(Error::Corruption { reason, .. }) if reason == "checksum mismatch"
+    ));
+    // a scan ends at the first error
+    let mut entries = db.iter().entries();
+    assert!(matches!(
+        entries.next(),
+        Some(Err(Error::Corruption { .. }))
+    ));
+    assert!(entries.next().is_none());
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn test_torn_write() {
+    let db = Database::new("/tmp/test_db_torn_write", true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    drop(db);
+
+    // lose the tail of the last record
+    let log = &files("/tmp/test_db_torn_write", FileType::Log)[0];
+    let file = fs::OpenOptions::new().write(true).open(log).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - 2).unwrap();
+    drop(file);
+
+    let db = Database::new("/tmp/test_db_torn_write", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert!(!db.has(b"def").unwrap());
+
+    db.put(b"ghi", b"rst").unwrap();
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_torn_write", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert_eq!(db.get(b"ghi").unwrap(), b"rst");
+    drop(db);
+
+    // a fresh log cut short in its header
+    let log = files("/tmp/test_db_torn_write", FileType::Log)
+        .pop()
+        .unwrap();
+    assert_eq!(fs::metadata(&log).unwrap().len(), log::HEADER_LEN);
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&log)
+        .unwrap()
+        .set_len(3)
+        .unwrap();
+    let db = Database::new("/tmp/test_db_torn_write", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert_eq!(db.get(b"ghi").unwrap(), b"rst");
+}
+
+#[test]
+fn test_log_corruption() {
+    let dir = "/tmp/test_log_corruption";
+    let db = Database::new(dir, true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    db.put(b"ghi", b"rst").unwrap();
+    drop(db);
+
+    // flip a byte of the middle record's value
+    let log = &files(dir, FileType::Log)[0];
+    let mut bytes = fs::read(log).unwrap();
+    let i = bytes.windows(3).position(|w| w == b"uvw").unwrap();
+    bytes[i] ^= 0xff;
+    fs::write(log, &bytes).unwrap();
+
+    // the error points at the start of the bad record, and the log
+    // stays as it was
+    assert!(matches!(
+        Database::new(dir, false),
+        Err(Error::Corruption { offset: Some(offset), reason })
+            if reason == "checksum mismatch"
+                && offset > log::HEADER_LEN
+                && offset < i as u64
+    ));
+    assert_eq!(fs::read(log).unwrap(), bytes);
+
+    // nothing is lost once it is mended
+    bytes[i] ^= 0xff;
+    fs::write(log, &bytes).unwrap();
+    let db = Database::new(dir, false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+    assert_eq!(db.get(b"ghi").unwrap(), b"rst");
+    db.put(b"jkl", b"opq").unwrap();
+    db.put(b"mno", b"lmn").unwrap();
+    drop(db);
+
+    // a torn record is only a crash's doing at the end of the last
+    // log, not in one with another after it
+    let log = files(dir, FileType::Log).pop().unwrap();
+    let bytes = fs::read(&log).unwrap();
+    let i = bytes.windows(3).position(|w| w == b"lmn").unwrap();
+    let later = file_name(Path::new(dir), 999, FileType::Log);
+    fs::write(&later, &bytes[..log::HEADER_LEN as usize]).unwrap();
+    fs::write(&log, &bytes[..i]).unwrap();
+    assert!(matches!(
+        Database::new(dir, false),
+        Err(Error::Corruption { reason, .. }) if reason.ends_with("cut short")
+    ));
+    assert!(log.exists() && later.exists());
+
+    // without one, it is dropped as a torn write
+    fs::remove_file(&later).unwrap();
+    let db = Database::new(dir, false).unwrap();
+    assert_eq!(db.get(b"jkl").unwrap(), b"opq");
+    assert!(!db.has(b"mno").unwrap());
+}
+
+#[test]
+fn test_write_batch() {
+    let dir = "/tmp/test_write_batch";
+    let db = Database::new(dir, true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    let mut batch = WriteBatch::new();
+    batch.put(b"def", b"uvw");
+    batch.delete(b"abc");
+    batch.put(b"def", b"123");
+    db.write(&batch).unwrap();
+    assert!(!db.has(b"abc").unwrap());
+    assert_eq!(db.get(b"def").unwrap(), b"123");
+
+    batch.clear();
+    batch.put(b"ghi", b"rst");
+    batch.put(b"jkl", b"opq");
+    db.write(&batch).unwrap();
+    drop(db);
+
+    // a torn batch is dropped whole
+    let log = &files(dir, FileType::Log)[0];
+    let file = fs::OpenOptions::new().write(true).open(log).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - 1).unwrap();
+    drop(file);
+
+    let db = Database::new(dir, false).unwrap();
+    assert!(!db.has(b"abc").unwrap());
+    assert_eq!(db.get(b"def").unwrap(), b"123");
+    assert!(!db.has(b"ghi").unwrap());
+    assert!(!db.has(b"jkl").unwrap());
+}
+
+#[test]
+fn test_recover_files() {
+    let dir = "/tmp/test_recover_files";
+    let db = Database::new(dir, true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.compact().unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    drop(db);
+
+    // left behind by a compaction that never made it to the manifest
+    let junk = file_name(Path::new(dir), 999, FileType::Table);
+    fs::write(&junk, b"junk").unwrap();
+
+    let db = Database::new(dir, false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+    assert!(!junk.exists());
+    assert_eq!(files(dir, FileType::Table).len(), 2);
+    assert_eq!(files(dir, FileType::Manifest).len(), 1);
+    drop(db);
+
+    // a table the manifest lists going missing is not
+    fs::remove_file(&files(dir, FileType::Table)[0]).unwrap();
+    assert!(matches!(
+        Database::new(dir, false),
+        Err(Error::Corruption { reason, .. }) if reason.starts_with("missing table")
+    ));
+}
+
+#[test]
+fn test_manifest_corruption() {
+    let dir = "/tmp/test_manifest_corruption";
+    let db = Database::new(dir, true).unwrap();
+    for i in 0..3 {
+        db.put(format!("key{i}").as_bytes(), b"value").unwrap();
+        db.compact().unwrap();
+    }
+    drop(db);
+    let tables = files(dir, FileType::Table);
+    let manifest = &files(dir, FileType::Manifest)[0];
+    let bytes = fs::read(manifest).unwrap();
+
+    // a bad record in the middle fails the open whatever the checks,
+    // without touching a table
+    let mut corrupted = bytes.clone();
+    corrupted[bytes.len() / 2] ^= 0xff;
+    fs::write(manifest, &corrupted).unwrap();
+    assert!(matches!(
+        Database::new(dir, false),
+        Err(Error::Corruption { .. })
+    ));
+    assert_eq!(files(dir, FileType::Table), tables);
+
+    // a torn last record is an edit that never took effect, the
+    // table it was adding may still be wanted
+    let mut torn = bytes.clone();
+    torn.extend_from_slice(&[0, 0, 0, 0, 100, b'x']);
+    fs::write(manifest, &torn).unwrap();
+    let junk = file_name(Path::new(dir), 999, FileType::Table);
+    fs::write(&junk, b"junk").unwrap();
+    let db = Database::new(dir, false).unwrap();
+    for i in 0..3 {
+        assert_eq!(db.get(format!("key{i}").as_bytes()).unwrap(), b"value");
+    }
+    assert!(junk.exists());
+    drop(db);
+
+    // the manifest written since is whole
+    let db = Database::new(dir, false).unwrap();
+    assert!(!junk.exists());
+    assert_eq!(db.get(b"key0").unwrap(), b"value");
+}
+
+#[test]
+fn test_flush() {
+    let options = Options {
+        write_buffer_size: 64,
+        level0_compaction_trigger: 1000,
+        level0_stop_writes_trigger: 1000,
+        ..Options::default()
+    };
+    let db = Database::with_options("/tmp/test_flush", true, options.clone())
+        .unwrap();
+    for i in 0..100 {
+        db.put(format!("key{i:03}").as_bytes(), b"0123456789")
+            .unwrap();
+    }
+    db.delete(b"key050").unwrap();
+    db.wait_for_background();
+    assert!(files("/tmp/test_flush", FileType::Table).len() > 1);
+    assert_eq!(files("/tmp/test_flush", FileType::Log).len(), 1);
+    assert_eq!(db.get(b"key000").unwrap(), b"0123456789");
+    assert!(!db.has(b"key050").unwrap());
+    drop(db);
+
+    let db = Database::with_options("/tmp/test_flush", false, options).unwrap();
+    assert_eq!(db.get(b"key099").unwrap(), b"0123456789");
+    assert!(!db.has(b"key050").unwrap());
+    assert_eq!(db.into_iter().count(), 99);
+}
+
+impl Inner {
+    // every version of every key, from the memtables and the tables
+    // of the current version
+    //
+    // with a prefix, only the tables that may hold keys starting
+    // with it are read
+    fn iter(&self, options: &ReadOptions, prefix: Option<&[u8]>) -> DBIterator {
+        let state = self.state.lock().unwrap();
+        let sequence = options
+            .snapshot
+            .map_or(state.versions.last_sequence(), Snapshot::sequence);
+        let mut sources: Vec<Source> = vec![Box::new(state.mem.iter())];
+        let mut range_tombstones = state.mem.range_tombstones();
+        if let Some(imm) = &state.imm {
+            sources.push(Box::new(imm.iter()));
+            range_tombstones.extend(imm.range_tombstones());
+        }
+        let version = state.versions.current();
+        drop(state);
+        // the tables left out of a prefix scan may still cover its keys
+        range_tombstones.extend(version.range_tombstones().cloned());
+        match prefix {
+            Some(prefix) => {
+                // every key starting with prefix has the same
+                // extracted prefix as it
+                let filter_key = self
+                    .options
+                    .prefix_extractor
+                    .as_ref()
+                    .and_then(|extractor| extractor.prefix(prefix))
+                    .map(|prefix| {
+                        internal_key(prefix, MAX_SEQUENCE, KIND_FOR_SEEK)
+                    });
+                sources.extend(version.prefix_iters(
+                    &self.table_cache,
+                    options,
+                    prefix,
+                    filter_key.as_deref(),
+                ));
+            }
+            None => sources.extend(version.iters(&self.table_cache, options)),
+        }
+        DBIterator::new(
+            MergingIterator::new(self.comparator.clone(), sources),
+            self.comparator.user_comparator().clone(),
+            sequence,
+            range_tombstones,
+            version,
+        )
+    }
+}
+
+impl IntoIterator for Database {
+    type Item = Result<(Vec<u8>, Vec<u8>), Error>;
+    type IntoIter = Entries;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter().entries()
+    }
+}
+
+#[test]
+fn test_iter() {
+    let db = Database::new("/tmp/test_db_iter", true).unwrap();
+    let numbers = ["one", "two", "three"];
+
+    for (i, n) in numbers.iter().enumerate() {
+        db.put((i + 1).to_string().as_bytes(), n.as_bytes())
+            .unwrap();
+    }
+
+    assert_eq!(
+        db.into_iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        vec![
+            (b"1".to_vec(), b"one".to_vec()),
+            (b"2".to_vec(), b"two".to_vec()),
+            (b"3".to_vec(), b"three".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn test_seek() {
+    let db = Database::new("/tmp/test_seek", true).unwrap();
+    for key in ["a", "b", "c", "d", "e", "f"] {
+        db.put(key.as_bytes(), b"old").unwrap();
+    }
+    db.compact().unwrap();
+    // newer versions and tombstones above the table
+    db.put(b"b", b"new").unwrap();
+    db.delete(b"c").unwrap();
+    db.delete(b"e").unwrap();
+    db.put(b"g", b"new").unwrap();
+
+    let mut iter = db.iter();
+    // later writes are not seen
+    db.put(b"h", b"new").unwrap();
+    let keys = |iter: &mut DBIterator, forward: bool| {
+        let mut keys = vec![];
+        while iter.valid() {
+            keys.push(String::from_utf8(iter.key().to_vec()).unwrap());
+            if forward {
+                iter.next();
+            } else {
+                iter.prev();
+            }
+        }
+        keys
+    };
+    iter.seek_to_first();
+    assert_eq!(keys(&mut iter, true), ["a", "b", "d", "f", "g"]);
+    iter.seek_to_last();
+    assert_eq!(keys(&mut iter, false), ["g", "f", "d", "b", "a"]);
+
+    iter.seek(b"c");
+    assert_eq!((iter.key(), iter.value()), (&b"d"[..], &b"old"[..]));
+    iter.prev();
+    assert_eq!((iter.key(), iter.value()), (&b"b"[..], &b"new"[..]));
+    iter.next();
+    assert_eq!(iter.key(), b"d");
+    iter.next();
+    assert_eq!(iter.key(), b"f");
+    iter.seek(b"z");
+    assert!(!iter.valid());
+    iter.status().unwrap();
+
+    let mut iter = db.iter().lower_bound(b"b").upper_bound(b"f");
+    iter.seek_to_first();
+    assert_eq!(keys(&mut iter, true), ["b", "d"]);
+    iter.seek_to_last();
+    assert_eq!(keys(&mut iter, false), ["d", "b"]);
+    iter.seek(b"a");
+    assert_eq!(iter.key(), b"b");
+    iter.seek(b"e");
+    assert!(!iter.valid());
+
+    // the database is still there
+    assert_eq!(db.get(b"h").unwrap(), b"new");
+    assert_eq!(db.iter().entries().count(), 6);
+}
+
+#[test]
+fn test_binary_safe() {
+    let db = Database::new("/tmp/test_db_binary_safe", true).unwrap();
+    let pairs: [(&[u8], &[u8]); 4] = [
+        (b"a,b", b"line\nbreak"),
+        (b"\n", b",,\n,"),
+        (b"\0\xff", b""),
+        (b"", b"empty key"),
+    ];
+
+    for (key, value) in pairs {
+        db.put(key, value).unwrap();
+    }
+    for (key, value) in pairs {
+        assert_eq!(db.get(key).unwrap(), value);
+    }
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_binary_safe", false).unwrap();
+    for (key, value) in pairs {
+        assert_eq!(db.get(key).unwrap(), value);
+    }
+    assert_eq!(
+        db.into_iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        [3, 2, 1, 0].map(|i| (pairs[i].0.to_vec(), pairs[i].1.to_vec()))
+    );
+}
+
+#[test]
+fn test_compact() {
+    let options = Options {
+        write_buffer_size: 256,
+        level0_compaction_trigger: 1000,
+        level0_stop_writes_trigger: 1000,
+        ..Options::default()
+    };
+    let db =
+        Database::with_options("/tmp/test_db_compact", true, options).unwrap();
+    for i in 0..100 {
+        db.put(b"abc", i.to_string().as_bytes()).unwrap();
+        db.put(i.to_string().as_bytes(), b"xyz").unwrap();
+    }
+    for i in 0..50 {
+        db.delete(i.to_string().as_bytes()).unwrap();
+    }
+    let size = |dir| {
+        files(dir, FileType::Table)
+            .iter()
+            .map(|path| fs::metadata(path).unwrap().len())
+            .sum::<u64>()
+    };
+    let before = size("/tmp/test_db_compact");
+
+    db.compact().unwrap();
+
+    assert_eq!(files("/tmp/test_db_compact", FileType::Table).len(), 1);
+    assert!(size("/tmp/test_db_compact") < before);
+    assert_eq!(db.get(b"abc").unwrap(), b"99");
+    assert!(!db.has(b"10").unwrap());
+    assert_eq!(db.get(b"60").unwrap(), b"xyz");
+
+    // writes keep working after the merge
+    db.put(b"def", b"uvw").unwrap();
+    drop(db);
+
+    let db = Database::new("/tmp/test_db_compact", false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"99");
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+    assert!(!db.has(b"10").unwrap());
+    assert_eq!(db.into_iter().count(), 52);
+}
+
+#[test]
+fn test_auto_compact() {
+    let options = Options {
+        write_buffer_size: 64,
+        level0_compaction_trigger: 3,
+        level0_stop_writes_trigger: 6,
+        ..Options::default()
+    };
+    let db = Database::with_options("/tmp/test_db_auto_compact", true, options)
+        .unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    for i in 0..1000 {
+        db.put(b"abc", i.to_string().as_bytes()).unwrap();
+        assert!(
+            db.inner.state.lock().unwrap().versions.current().files[0].len()
+                < 6
+        );
+    }
+    db.wait_for_background();
+    assert!(
+        db.inner.state.lock().unwrap().versions.current().files[0].len() < 3
+    );
+    assert!(files("/tmp/test_db_auto_compact", FileType::Table).len() < 6);
+    assert_eq!(db.get(b"abc").unwrap(), b"999");
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+}
+
+#[test]
+fn test_leveled() {
+    let options = Options {
+        write_buffer_size: 1024,
+        max_bytes_for_level_base: 4096,
+        max_file_size: 1024,
+        ..Options::default()
+    };
+    let db = Database::with_options("/tmp/test_leveled", true, options.clone())
+        .unwrap();
+    for round in 0..3 {
+        for i in 0..1000 {
+            let key = format!("key{:04}", i * 7 % 1000);
+            db.put(key.as_bytes(), format!("{round}-{i}").as_bytes())
+                .unwrap();
+        }
+    }
+    for i in 0..100 {
+        db.delete(format!("key{i:04}").as_bytes()).unwrap();
+    }
+    db.wait_for_background();
+
+    let version = db.inner.state.lock().unwrap().versions.current();
+    assert!(version.files[2..].iter().any(|files| !files.is_empty()));
+    for files in &version.files[1..] {
+        assert!(files.windows(2).all(|w| w[0].largest < w[1].smallest));
+    }
+    drop(version);
+    assert_eq!(db.get(b"key0107").unwrap(), b"2-301");
+    assert!(!db.has(b"key0050").unwrap());
+    drop(db);
+
+    // the levels are picked up again on open
+    let db =
+        Database::with_options("/tmp/test_leveled", false, options).unwrap();
+    let levels = db
+        .inner
+        .state
+        .lock()
+        .unwrap()
+        .versions
+        .current()
+        .files
+        .clone();
+    assert!(levels[1..].iter().any(|files| !files.is_empty()));
+    assert_eq!(db.get(b"key0999").unwrap(), b"2-857");
+    let entries = db.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(entries.len(), 900);
+    assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+}
+
+#[test]
+fn test_snapshot() {
+    let dir = "/tmp/test_snapshot";
+    let db = Database::new(dir, true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    let snapshot = db.snapshot();
+    db.put(b"abc", b"123").unwrap();
+    db.delete(b"def").unwrap();
+    db.put(b"ghi", b"rst").unwrap();
+
+    assert_eq!(db.get(b"abc").unwrap(), b"123");
+    assert_eq!(db.get_at(b"abc", &snapshot).unwrap(), b"xyz");
+    assert_eq!(db.get_at(b"def", &snapshot).unwrap(), b"uvw");
+    assert!(matches!(
+        db.get_at(b"ghi", &snapshot),
+        Err(Error::KeyNotFound)
+    ));
+
+    // the versions the snapshot sees survive compactions
+    for i in 0..100 {
+        db.put(b"abc", i.to_string().as_bytes()).unwrap();
+    }
+    db.compact().unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"99");
+    assert_eq!(db.get_at(b"abc", &snapshot).unwrap(), b"xyz");
+    let options = ReadOptions::default()
+        .snapshot(Some(&snapshot))
+        .fill_cache(false);
+    assert_eq!(db.get_with_options(b"def", &options).unwrap(), b"uvw");
+    assert_eq!(
+        db.iter_at(&snapshot)
+            .entries()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        [
+            (b"abc".to_vec(), b"xyz".to_vec()),
+            (b"def".to_vec(), b"uvw".to_vec()),
+        ]
+    );
+
+    // and go once it is released
+    drop(snapshot);
+    db.put(b"abc", b"100").unwrap();
+    db.compact().unwrap();
+    let version = db.inner.state.lock().unwrap().versions.current();
+    let last_level = version.files.last().unwrap().clone();
+    let last_level = version.level_iter(
+        &db.inner.table_cache,
+        &ReadOptions::default(),
+        last_level,
+    );
+    assert_eq!(iterator::entries(last_level).count(), 2);
+    drop(db);
+
+    // sequence numbers pick up where they left off
+    let db = Database::new(dir, false).unwrap();
+    db.put(b"abc", b"101").unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"101");
+}
+
+#[test]
+fn test_comparator() {
+    struct ReverseComparator;
+
+    impl Comparator for ReverseComparator {
+        fn name(&self) -> &str {
+            "test.ReverseComparator"
+        }
+
+        fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+            b.cmp(a)
+        }
+    }
+
+    let dir = "/tmp/test_comparator";
+    let options = Options {
+        comparator: Arc::new(ReverseComparator),
+        ..Options::default()
+    };
+    let db = Database::with_options(dir, true, options.clone()).unwrap();
+    // enough for the tables to have a few blocks
+    for i in 0..1000 {
+        db.put(format!("{i:04}").as_bytes(), &[b'x'; 100]).unwrap();
+    }
+    db.delete(b"0500").unwrap();
+    db.compact().unwrap();
+    assert!(matches!(db.get(b"0500"), Err(Error::KeyNotFound)));
+    assert_eq!(db.get(b"0501").unwrap(), [b'x'; 100]);
+    let keys = db
+        .iter()
+        .entries()
+        .map(|entry| entry.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(keys.len(), 999);
+    assert_eq!(keys[0], b"0999");
+    assert!(keys.windows(2).all(|w| w[0] > w[1]));
+    // the keys with a prefix aren't kept together
+    assert!(matches!(
+        db.scan_prefix(b"05"),
+        Err(Error::InvalidArgument(_))
+    ));
+    drop(db);
+
+    // the comparator has to match the one the database was created with
+    assert!(matches!(
+        Database::new(dir, false),
+        Err(Error::InvalidArgument(_))
+    ));
+    let db = Database::with_options(dir, false, options).unwrap();
+    assert_eq!(db.get(b"0999").unwrap(), [b'x'; 100]);
+}
+
+#[test]
+fn test_scan_prefix() {
+    use crate::filter::FixedPrefix;
+
+    let options = Options {
+        write_buffer_size: 64,
+        level0_compaction_trigger: 1000,
+        level0_stop_writes_trigger: 1000,
+        prefix_extractor: Some(Arc::new(FixedPrefix::new(8))),
+        ..Options::default()
+    };
+    let db =
+        Database::with_options("/tmp/test_scan_prefix", true, options).unwrap();
+    // tables around user:42: without any of its keys
+    for i in 0..20 {
+        db.put(format!("user:40:{i:02}").as_bytes(), b"x").unwrap();
+        db.put(format!("user:50:{i:02}").as_bytes(), b"x").unwrap();
+    }
+    for i in 0..5 {
+        db.put(format!("user:42:{i:02}").as_bytes(), b"y").unwrap();
+    }
+    let snapshot = db.snapshot();
+    db.delete(b"user:42:03").unwrap();
+    db.put(b"user:42", b"z").unwrap();
+    db.wait_for_background();
+
+    let keys = |prefix: &[u8]| {
+        db.scan_prefix(prefix)
+            .unwrap()
+            .map(|entry| String::from_utf8(entry.unwrap().0).unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        keys(b"user:42:"),
+        ["user:42:00", "user:42:01", "user:42:02", "user:42:04"]
+    );
+    assert_eq!(keys(b"user:42:04"), ["user:42:04"]);
+    assert!(keys(b"user:43").is_empty());
+    // shorter than the extractor's prefix, no filter to go by
+    assert_eq!(keys(b"user:4").len(), 25);
+    let options = ReadOptions::default().snapshot(Some(&snapshot));
+    assert_eq!(
+        db.scan_prefix_with_options(b"user:42:", &options)
+            .unwrap()
+            .count(),
+        5
+    );
+
+    // the filters rule out most of the tables
+    let version = db.inner.state.lock().unwrap().versions.current();
+    let cache = &db.inner.table_cache;
+    let filter_key = internal_key(b"user:42:", MAX_SEQUENCE, KIND_FOR_SEEK);
+    let all = version.iters(cache, &ReadOptions::default()).len();
+    let scanned = version
+        .prefix_iters(
+            cache,
+            &ReadOptions::default(),
+            b"user:42:",
+            Some(&filter_key),
+        )
+        .len();
+    assert!(scanned < all / 2, "{scanned} of {all} tables scanned");
+}
+
+#[test]
+fn test_delete_range() {
+    let dir = "/tmp/test_delete_range";
+    // small tables, so the range deletion gets cut up between them
+    let options = Options {
+        max_file_size: 1024,
+        ..Options::default()
+    };
+    let db = Database::with_options(dir, true, options.clone()).unwrap();
+    let key = |i: usize| format!("key{i:03}").into_bytes();
+    for i in 0..100 {
+        db.put(&key(i), &[b'x'; 50]).unwrap();
+    }
+    db.compact().unwrap();
+    db.put(&key(30), b"before").unwrap();
+    let snapshot = db.snapshot();
+    db.delete_range(&key(20), &key(60)).unwrap();
+    db.put(&key(40), b"after").unwrap();
+    assert!(matches!(
+        db.delete_range(b"b", b"a"),
+        Err(Error::InvalidArgument(_))
+    ));
+
+    let check = |db: &Database| {
+        let keys = db
+            .iter()
+            .entries()
+            .map(|entry| entry.unwrap().0)
+            .collect::<Vec<_>>();
+        let live = (0..20).chain([40]).chain(60..100);
+        assert_eq!(keys, live.map(key).collect::<Vec<_>>());
+        assert!(!db.has(&key(20)).unwrap());
+        assert!(!db.has(&key(30)).unwrap());
+        assert!(!db.has(&key(59)).unwrap());
+        assert_eq!(db.get(&key(40)).unwrap(), b"after");
+        assert!(db.has(&key(60)).unwrap());
+        let mut iter = db.iter();
+        iter.seek(&key(59));
+        assert_eq!(iter.key(), key(60));
+        iter.prev();
+        assert_eq!(iter.key(), key(40));
+        iter.prev();
+        assert_eq!(iter.key(), key(19));
+    };
+    check(&db);
+    assert_eq!(db.get_at(&key(30), &snapshot).unwrap(), b"before");
+
+    // the snapshot keeps it and what it covers around
+    db.compact().unwrap();
+    check(&db);
+    assert_eq!(db.get_at(&key(30), &snapshot).unwrap(), b"before");
+    assert_eq!(db.iter_at(&snapshot).entries().count(), 100);
+    let version = db.inner.state.lock().unwrap().versions.current();
+    let last_level = version.files.last().unwrap();
+    assert!(last_level.len() > 1);
+    // cut up, the tables only meet where one range ends and the
+    // next one starts
+    assert!(last_level.windows(2).all(|pair| {
+        let comparator = &db.inner.comparator;
+        comparator.compare(&pair[0].largest, &pair[1].smallest)
+            != Ordering::Greater
+    }));
+    assert!(version.range_tombstones().count() > 1);
+    drop(version);
+    drop(snapshot);
+    drop(db);
+
+    let db = Database::with_options(dir, false, options).unwrap();
+    check(&db);
+    // a compaction over the whole range finally drops it all
+    db.put(&key(0), &[b'x'; 50]).unwrap();
+    db.put(&key(99), &[b'x'; 50]).unwrap();
+    db.compact().unwrap();
+    check(&db);
+    let version = db.inner.state.lock().unwrap().versions.current();
+    assert_eq!(version.range_tombstones().count(), 0);
+    let entries = version.files.iter().map(|files| {
+        let files = version.level_iter(
+            &db.inner.table_cache,
+            &ReadOptions::default(),
+            files.clone(),
+        );
+        iterator::entries(files).count()
+    });
+    assert_eq!(entries.sum::<usize>(), 61);
+}
+
+#[test]
+fn test_open() {
+    let dir = "/tmp/test_open";
+    let _ = fs::remove_dir_all(dir);
+    let invalid = |result: Result<Database, Error>| {
+        matches!(result, Err(Error::InvalidArgument(_)))
+    };
+    assert!(invalid(Database::open(dir, &Options::default())));
+    assert!(invalid(Database::open(
+        dir,
+        &Options::default().create_if_missing(true).block_size(0)
+    )));
+    // neither of the failed opens left anything behind
+    assert!(!Path::new(dir).exists());
+    fs::create_dir(dir).unwrap();
+    assert!(invalid(Database::open(dir, &Options::default())));
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 0);
+
+    let options = Options::default().create_if_missing(true);
+    let db = Database::open(dir, &options).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    db.put(b"def", b"uvw").unwrap();
+    drop(db);
+    assert!(invalid(Database::open(
+        dir,
+        &options.clone().error_if_exists(true)
+    )));
+
+    // a bad record fails the open, paranoid or not, a whole one
+    // isn't a torn write
+    let log = &files(dir, FileType::Log)[0];
+    let mut bytes = fs::read(log).unwrap();
+    let len = bytes.len();
+    bytes[len - 1] ^= 0xff;
+    fs::write(log, &bytes).unwrap();
+    for options in [options.clone().paranoid_checks(true), Options::default()] {
+        assert!(matches!(
+            Database::open(dir, &options),
+            Err(Error::Corruption { reason, .. })
+                if reason == "checksum mismatch"
+        ));
+    }
+}
+
+#[test]
+fn test_sync() {
+    let dir = "/tmp/test_sync";
+    let options = Options::default()
+        .sync_interval(Some(std::time::Duration::from_millis(1)));
+    let db = Database::with_options(dir, true, options.clone()).unwrap();
+    let mut batch = WriteBatch::new();
+    batch.put(b"abc", b"xyz");
+    db.write_with_options(&batch, &WriteOptions::default().sync(true))
+        .unwrap();
+    assert!(!db.inner.state.lock().unwrap().log_dirty);
+    db.put(b"def", b"uvw").unwrap();
+    // the sync thread gets to it
+    while db.inner.state.lock().unwrap().log_dirty {
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    drop(db);
+
+    let db = Database::with_options(dir, false, options).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert_eq!(db.get(b"def").unwrap(), b"uvw");
+}
+
+#[test]
+fn test_block_cache() {
+    let dir = "/tmp/test_block_cache";
+    let db = Database::new(dir, true).unwrap();
+    for i in 0..1000 {
+        let key = format!("key{i:04}");
+        db.put(key.as_bytes(), b"0123456789").unwrap();
+    }
+    db.compact().unwrap();
+
+    assert_eq!(db.get(b"key0500").unwrap(), b"0123456789");
+    let stats = db.block_cache_stats();
+    assert!(stats.misses > 0);
+    assert!(stats.usage > 0 && stats.usage <= stats.capacity);
+    assert_eq!(db.get(b"key0500").unwrap(), b"0123456789");
+    assert_eq!(db.block_cache_stats().hits, stats.hits + 1);
+
+    // a scan that doesn't fill the cache leaves it as it was
+    let options = ReadOptions::default().fill_cache(false);
+    assert_eq!(db.iter_with_options(&options).entries().count(), 1000);
+    assert_eq!(db.block_cache_stats().usage, stats.usage);
+    // and so does one that doesn't check the blocks it reads
+    let options = ReadOptions::default().verify_checksums(false);
+    assert_eq!(db.iter_with_options(&options).entries().count(), 1000);
+    assert_eq!(db.block_cache_stats().usage, stats.usage);
+    drop(db);
+
+    let options = Options::default().block_cache_capacity(0);
+    let db = Database::with_options(dir, false, options).unwrap();
+    assert_eq!(db.get(b"key0500").unwrap(), b"0123456789");
+    assert_eq!(db.block_cache_stats(), CacheStats::default());
+}
+
+#[test]
+fn test_concurrent() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Database>();
+    assert_send_sync::<Snapshot>();
+    fn assert_send<T: Send>() {}
+    assert_send::<DBIterator>();
+
+    let options = Options::default().write_buffer_size(16 << 10);
+    let db = Arc::new(
+        Database::with_options("/tmp/test_concurrent", true, options).unwrap(),
+    );
+    let writers = (0..4).map(|t| {
+        let db = db.clone();
+        thread::spawn(move || {
+            for i in 0..500 {
+                let key = format!("key{t}.{i:04}");
+                db.put(key.as_bytes(), b"0123456789").unwrap();
+            }
+        })
+    });
+    let readers = (0..4).map(|t| {
+        let db = db.clone();
+        thread::spawn(move || {
+            for i in 0..500 {
+                // whatever a reader sees was written whole
+                let key = format!("key{t}.{i:04}");
+                if let Ok(value) = db.get(key.as_bytes()) {
+                    assert_eq!(value, b"0123456789");
+                }
+                let entries = db.iter().entries().take(10);
+                for entry in entries {
+                    assert_eq!(entry.unwrap().1, b"0123456789");
+                }
+            }
+        })
+    });
+    let threads = writers.chain(readers).collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    db.compact().unwrap();
+    assert_eq!(db.iter().entries().count(), 2000);
+    assert_eq!(db.get(b"key3.0499").unwrap(), b"0123456789");
+
+    // a compaction is done with the tables there were when it
+    // started, however many writes keep coming
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let writer = {
+        let (db, done) = (db.clone(), done.clone());
+        thread::spawn(move || {
+            let mut i = 0;
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                db.put(format!("more{i:06}").as_bytes(), &[b'x'; 100])
+                    .unwrap();
+                i += 1;
+            }
+        })
+    };
+    thread::sleep(std::time::Duration::from_millis(50));
+    db.compact().unwrap();
+    done.store(true, std::sync::atomic::Ordering::Relaxed);
+    writer.join().unwrap();
+}
+
+#[test]
+fn test_group_commit() {
+    let db = Database::new("/tmp/test_group_commit", true).unwrap();
+    let inner = &db.inner;
+    let mut state = inner.state.lock().unwrap();
+    for (i, sync) in [false, false, true].into_iter().enumerate() {
+        let mut batch = WriteBatch::new();
+        batch.put(format!("key{i}").as_bytes(), b"value");
+        state.writers.push_back(PendingWrite {
+            id: 100 + i as u64,
+            batch: Some(batch),
+            sync,
+            cv: Arc::new(Condvar::new()),
+        });
+    }
+    // the synced one waits for a leader that syncs
+    let (mut state, result, taken) = inner.write_group(state);
+    result.unwrap();
+    assert_eq!(taken, 2);
+    assert_eq!(state.versions.last_sequence(), 2);
+    state.writers.drain(..2);
+    let (mut state, result, taken) = inner.write_group(state);
+    result.unwrap();
+    assert_eq!(taken, 1);
+    state.writers.clear();
+    drop(state);
+    for key in [b"key0", b"key1", b"key2"] {
+        assert_eq!(db.get(key).unwrap(), b"value");
+    }
+
+    // one record for the first two, one for the last
+    let log = files("/tmp/test_group_commit", FileType::Log)
+        .pop()
+        .unwrap();
+    let mut records = 0;
+    log::replay(&log, |_| {
+        records += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(records, 2);
+}
+
+#[test]
+fn test_lock() {
+    let dir = "/tmp/test_lock";
+    let db = Database::new(dir, true).unwrap();
+    db.put(b"abc", b"xyz").unwrap();
+    assert!(matches!(Database::new(dir, false), Err(Error::Locked)));
+    // truncating doesn't get past the lock either
+    assert!(matches!(Database::new(dir, true), Err(Error::Locked)));
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    drop(db);
+
+    let db = Database::new(dir, false).unwrap();
+    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
+    assert!(Path::new(dir).join("LOCK").exists());
+}
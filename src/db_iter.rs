@@ -0,0 +1,382 @@
+use crate::comparator::Comparator;
+use crate::dbformat::{
+    internal_key, parse_internal_key, user_key, RecordKind, KIND_FOR_SEEK,
+    MAX_SEQUENCE,
+};
+use crate::iterator::InternalIterator;
+use crate::merge::MergingIterator;
+use crate::range_del::{FragmentedTombstones, RangeTombstone};
+use crate::version::Version;
+use crate::Error;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Reverse,
+}
+
+// cursor over the live entries of the database as of some sequence
+// number, in key order, within [lower bound, upper bound) if set
+//
+// the merging iterator underneath yields every version of every key,
+// going forward it is at the newest visible version of the current
+// key, going backwards it is before all versions of it and the
+// current entry is copied out
+pub struct DBIterator {
+    inner: MergingIterator,
+    user_comparator: Arc<dyn Comparator>,
+    sequence: u64,
+    // of the memtables and the tables, all of them
+    range_tombstones: FragmentedTombstones,
+    // the tables being read from stay around for as long as it does
+    _version: Arc<Version>,
+    lower_bound: Option<Vec<u8>>,
+    upper_bound: Option<Vec<u8>>,
+    // keys not starting with it are left out
+    prefix: Option<Vec<u8>>,
+    direction: Direction,
+    valid: bool,
+    // the user key of the current entry when going backwards, scratch
+    // space for the key being skipped going forward
+    saved_key: Vec<u8>,
+    saved_value: Vec<u8>,
+    err: Option<Error>,
+}
+
+impl DBIterator {
+    pub(crate) fn new(
+        inner: MergingIterator,
+        user_comparator: Arc<dyn Comparator>,
+        sequence: u64,
+        range_tombstones: Vec<RangeTombstone>,
+        version: Arc<Version>,
+    ) -> Self {
+        let range_tombstones = FragmentedTombstones::new(
+            user_comparator.as_ref(),
+            &range_tombstones,
+        );
+        Self {
+            inner,
+            user_comparator,
+            sequence,
+            range_tombstones,
+            _version: version,
+            lower_bound: None,
+            upper_bound: None,
+            prefix: None,
+            direction: Direction::Forward,
+            valid: false,
+            saved_key: vec![],
+            saved_value: vec![],
+            err: None,
+        }
+    }
+
+    // keys before this one are left out
+    pub fn lower_bound(mut self, key: &[u8]) -> Self {
+        self.lower_bound = Some(key.to_vec());
+        self
+    }
+
+    // keys from this one on are left out
+    pub fn upper_bound(mut self, key: &[u8]) -> Self {
+        self.upper_bound = Some(key.to_vec());
+        self
+    }
+
+    // only keys starting with prefix, which the comparator has to
+    // keep together as the bytewise one does
+    pub(crate) fn prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = Some(prefix.to_vec());
+        self.lower_bound(prefix)
+    }
+
+    pub fn valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn key(&self) -> &[u8] {
+        assert!(self.valid);
+        match self.direction {
+            Direction::Forward => user_key(self.inner.key()),
+            Direction::Reverse => &self.saved_key,
+        }
+    }
+
+    pub fn value(&self) -> &[u8] {
+        assert!(self.valid);
+        match self.direction {
+            Direction::Forward => self.inner.value(),
+            Direction::Reverse => &self.saved_value,
+        }
+    }
+
+    pub fn seek_to_first(&mut self) {
+        match self.lower_bound.clone() {
+            Some(lower_bound) => self.seek(&lower_bound),
+            None => {
+                self.direction = Direction::Forward;
+                self.inner.seek_to_first();
+                self.find_next_user_entry(false);
+                self.check_upper_bound();
+            }
+        }
+    }
+
+    pub fn seek_to_last(&mut self) {
+        self.direction = Direction::Reverse;
+        match &self.upper_bound {
+            // every version of the bound itself is left out too
+            Some(upper_bound) => {
+                let upper_bound =
+                    internal_key(upper_bound, MAX_SEQUENCE, KIND_FOR_SEEK);
+                self.inner.seek(&upper_bound);
+                if self.inner.valid() {
+                    self.inner.prev();
+                } else {
+                    self.inner.seek_to_last();
+                }
+            }
+            None => self.inner.seek_to_last(),
+        }
+        self.find_prev_user_entry();
+        self.check_lower_bound();
+    }
+
+    // positions at the first key >= target
+    pub fn seek(&mut self, target: &[u8]) {
+        let target = match &self.lower_bound {
+            Some(lower_bound)
+                if self.user_comparator.compare(target, lower_bound)
+                    == Ordering::Less =>
+            {
+                lower_bound.clone()
+            }
+            _ => target.to_vec(),
+        };
+        self.direction = Direction::Forward;
+        self.inner
+            .seek(&internal_key(&target, self.sequence, KIND_FOR_SEEK));
+        self.find_next_user_entry(false);
+        self.check_upper_bound();
+    }
+
+    pub fn next(&mut self) {
+        assert!(self.valid);
+        if self.direction == Direction::Reverse {
+            // the merging iterator is just before the entries of the
+            // current key, which saved_key already holds
+            self.direction = Direction::Forward;
+            if self.inner.valid() {
+                self.inner.next();
+            } else {
+                self.inner.seek_to_first();
+            }
+        } else {
+            self.saved_key.clear();
+            self.saved_key.extend_from_slice(user_key(self.inner.key()));
+            self.inner.next();
+        }
+        self.find_next_user_entry(true);
+        self.check_upper_bound();
+    }
+
+    pub fn prev(&mut self) {
+        assert!(self.valid);
+        if self.direction == Direction::Forward {
+            // back up to just before the entries of the current key
+            self.saved_key.clear();
+            self.saved_key.extend_from_slice(user_key(self.inner.key()));
+            loop {
+                self.inner.prev();
+                if !self.inner.valid() {
+                    break;
+                }
+                if self
+                    .user_comparator
+                    .compare(user_key(self.inner.key()), &self.saved_key)
+                    == Ordering::Less
+                {
+                    break;
+                }
+            }
+            self.direction = Direction::Reverse;
+        }
+        self.find_prev_user_entry();
+        self.check_lower_bound();
+    }
+
+    // the first error met while moving around, if any
+    pub fn status(&mut self) -> Result<(), Error> {
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+        self.inner.status()
+    }
+
+    // all the entries from the first one on
+    pub fn entries(mut self) -> Entries {
+        self.seek_to_first();
+        Entries {
+            iter: self,
+            done: false,
+        }
+    }
+
+    // the sequence number and kind of the entry the merging iterator
+    // is at, None if its key doesn't parse, a value some range
+    // deletion hides counts as a deletion
+    fn parse_current(&mut self) -> Option<(u64, RecordKind)> {
+        match parse_internal_key(self.inner.key()) {
+            Some((key, sequence, RecordKind::Value))
+                if self
+                    .range_tombstones
+                    .newest_covering(
+                        self.user_comparator.as_ref(),
+                        key,
+                        self.sequence,
+                    )
+                    .is_some_and(|covering| covering > sequence) =>
+            {
+                Some((sequence, RecordKind::Deletion))
+            }
+            Some((_, sequence, kind)) => Some((sequence, kind)),
+            None => {
+                self.err = Some(Error::Corruption {
+                    offset: None,
+                    reason: "bad internal key".to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    // goes forward to the newest visible version of the next key
+    // that wasn't deleted, skipping saved_key and everything before
+    // it if skipping is set
+    fn find_next_user_entry(&mut self, mut skipping: bool) {
+        while self.inner.valid() {
+            if let Some((sequence, kind)) = self.parse_current() {
+                if sequence <= self.sequence {
+                    let key = user_key(self.inner.key());
+                    match kind {
+                        RecordKind::Deletion => {
+                            // every older version of key is hidden
+                            self.saved_key.clear();
+                            self.saved_key.extend_from_slice(key);
+                            skipping = true;
+                        }
+                        RecordKind::Value => {
+                            let hidden = skipping
+                                && self
+                                    .user_comparator
+                                    .compare(key, &self.saved_key)
+                                    != Ordering::Greater;
+                            if !hidden {
+                                self.valid = true;
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+            self.inner.next();
+        }
+        self.valid = false;
+    }
+
+    // goes backwards through every version of the previous key, the
+    // last visible one seen is the newest, and on to the key before
+    // it if that one is a deletion
+    fn find_prev_user_entry(&mut self) {
+        let mut kind = RecordKind::Deletion;
+        while self.inner.valid() {
+            if let Some((sequence, entry_kind)) = self.parse_current() {
+                if sequence <= self.sequence {
+                    let key = user_key(self.inner.key());
+                    if kind != RecordKind::Deletion
+                        && self.user_comparator.compare(key, &self.saved_key)
+                            == Ordering::Less
+                    {
+                        // a live entry of the key after this one
+                        break;
+                    }
+                    kind = entry_kind;
+                    self.saved_key.clear();
+                    self.saved_value.clear();
+                    if kind == RecordKind::Value {
+                        self.saved_key.extend_from_slice(key);
+                        self.saved_value.extend_from_slice(self.inner.value());
+                    }
+                }
+            }
+            self.inner.prev();
+        }
+        self.valid = kind == RecordKind::Value;
+        if !self.valid {
+            self.direction = Direction::Forward;
+        }
+    }
+
+    fn check_upper_bound(&mut self) {
+        self.check_prefix();
+        if let Some(upper_bound) = &self.upper_bound {
+            if self.valid
+                && self.user_comparator.compare(self.key(), upper_bound)
+                    != Ordering::Less
+            {
+                self.valid = false;
+            }
+        }
+    }
+
+    fn check_lower_bound(&mut self) {
+        self.check_prefix();
+        if let Some(lower_bound) = &self.lower_bound {
+            if self.valid
+                && self.user_comparator.compare(self.key(), lower_bound)
+                    == Ordering::Less
+            {
+                self.valid = false;
+            }
+        }
+    }
+
+    fn check_prefix(&mut self) {
+        if let Some(prefix) = &self.prefix {
+            if self.valid && !self.key().starts_with(prefix) {
+                self.valid = false;
+            }
+        }
+    }
+}
+
+// the entries of a DBIterator from where it is on, copied
+pub struct Entries {
+    iter: DBIterator,
+    // set after an error, there is no telling what comes next
+    done: bool,
+}
+
+impl std::iter::FusedIterator for Entries {}
+
+impl Iterator for Entries {
+    type Item = Result<(Vec<u8>, Vec<u8>), Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Err(e) = self.iter.status() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        if !self.iter.valid() {
+            return None;
+        }
+        let entry = (self.iter.key().to_vec(), self.iter.value().to_vec());
+        self.iter.next();
+        Some(Ok(entry))
+    }
+}
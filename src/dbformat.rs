@@ -0,0 +1,227 @@
+use crate::comparator::Comparator;
+use crate::filter::{FilterPolicy, PrefixExtractor};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+// the tag packs the sequence number and the kind into a u64,
+// the kind takes the low byte
+pub const MAX_SEQUENCE: u64 = (1 << 56) - 1;
+const TAG_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Value = 0,
+    // tombstone, carries no value
+    Deletion = 1,
+}
+
+// entries of a user key with the same sequence number are ordered
+// by kind, highest first, so seeking with this one lands before all
+pub const KIND_FOR_SEEK: RecordKind = RecordKind::Deletion;
+
+impl RecordKind {
+    pub fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Value),
+            1 => Some(Self::Deletion),
+            _ => None,
+        }
+    }
+}
+
+// internal key layout:
+// user key | (sequence << 8 | kind) as u64
+//
+// every write gets its own sequence number, so there is one internal
+// key per version of a user key
+pub fn internal_key(
+    user_key: &[u8],
+    sequence: u64,
+    kind: RecordKind,
+) -> Vec<u8> {
+    debug_assert!(sequence <= MAX_SEQUENCE);
+    let mut key = Vec::with_capacity(user_key.len() + TAG_LEN);
+    key.extend_from_slice(user_key);
+    key.extend_from_slice(&(sequence << 8 | kind as u64).to_le_bytes());
+    key
+}
+
+// None if key is too short or has an unknown kind
+pub fn parse_internal_key(key: &[u8]) -> Option<(&[u8], u64, RecordKind)> {
+    let split = key.len().checked_sub(TAG_LEN)?;
+    let tag = u64::from_le_bytes(key[split..].try_into().unwrap());
+    let kind = RecordKind::from_u8(tag as u8)?;
+    Some((&key[..split], tag >> 8, kind))
+}
+
+pub fn user_key(key: &[u8]) -> &[u8] {
+    &key[..key.len().saturating_sub(TAG_LEN)]
+}
+
+fn tag(key: &[u8]) -> u64 {
+    match key.len().checked_sub(TAG_LEN) {
+        Some(split) => u64::from_le_bytes(key[split..].try_into().unwrap()),
+        None => 0,
+    }
+}
+
+// orders internal keys by user key, then newest first
+pub struct InternalKeyComparator {
+    user: Arc<dyn Comparator>,
+}
+
+impl InternalKeyComparator {
+    pub fn new(user: Arc<dyn Comparator>) -> Self {
+        Self { user }
+    }
+
+    pub fn user_comparator(&self) -> &Arc<dyn Comparator> {
+        &self.user
+    }
+}
+
+impl Comparator for InternalKeyComparator {
+    fn name(&self) -> &str {
+        "reberu.InternalKeyComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        self.user
+            .compare(user_key(a), user_key(b))
+            .then_with(|| tag(b).cmp(&tag(a)))
+    }
+
+    // shortens the user key, a shorter one that sorts after it gets
+    // the highest tag to land before every version of itself
+    fn find_shortest_separator(&self, start: &mut Vec<u8>, limit: &[u8]) {
+        let mut user_start = user_key(start).to_vec();
+        self.user
+            .find_shortest_separator(&mut user_start, user_key(limit));
+        self.shorten(start, user_start);
+    }
+
+    fn find_short_successor(&self, key: &mut Vec<u8>) {
+        let mut user_key = user_key(key).to_vec();
+        self.user.find_short_successor(&mut user_key);
+        self.shorten(key, user_key);
+    }
+}
+
+impl InternalKeyComparator {
+    fn shorten(&self, key: &mut Vec<u8>, shorter: Vec<u8>) {
+        let current = user_key(key);
+        if shorter.len() < current.len()
+            && self.user.compare(current, &shorter) == Ordering::Less
+        {
+            *key = internal_key(&shorter, MAX_SEQUENCE, KIND_FOR_SEEK);
+        }
+    }
+}
+
+// tables hold internal keys but their filters only ever get asked
+// about user keys, under the name of the policy it wraps
+//
+// with a prefix extractor the prefixes of the keys go in too, and the
+// name says which extractor made them
+pub struct InternalFilterPolicy {
+    user: Arc<dyn FilterPolicy>,
+    prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    name: String,
+}
+
+impl InternalFilterPolicy {
+    pub fn new(
+        user: Arc<dyn FilterPolicy>,
+        prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    ) -> Self {
+        let name = match &prefix_extractor {
+            Some(extractor) => format!("{}+{}", user.name(), extractor.name()),
+            None => user.name().to_string(),
+        };
+        Self {
+            user,
+            prefix_extractor,
+            name,
+        }
+    }
+}
+
+impl FilterPolicy for InternalFilterPolicy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn create_filter(&self, keys: &[&[u8]]) -> Vec<u8> {
+        let mut user_keys = vec![];
+        let mut last_prefix = None;
+        for key in keys {
+            let key = user_key(key);
+            user_keys.push(key);
+            // keys come sorted, so each prefix once is enough
+            let prefix = self
+                .prefix_extractor
+                .as_ref()
+                .and_then(|extractor| extractor.prefix(key));
+            if prefix.is_some() && prefix != last_prefix {
+                user_keys.extend(prefix);
+                last_prefix = prefix;
+            }
+        }
+        self.user.create_filter(&user_keys)
+    }
+
+    fn key_may_match(&self, key: &[u8], filter: &[u8]) -> bool {
+        self.user.key_may_match(user_key(key), filter)
+    }
+}
+
+#[test]
+fn test_internal_key() {
+    use crate::comparator::BytewiseComparator;
+
+    let key = internal_key(b"foo", 100, RecordKind::Value);
+    assert_eq!(
+        parse_internal_key(&key),
+        Some((&b"foo"[..], 100, RecordKind::Value))
+    );
+    assert_eq!(user_key(&key), b"foo");
+    assert_eq!(parse_internal_key(b"short"), None);
+    let key = internal_key(b"", MAX_SEQUENCE, RecordKind::Deletion);
+    assert_eq!(
+        parse_internal_key(&key),
+        Some((&b""[..], MAX_SEQUENCE, RecordKind::Deletion))
+    );
+
+    let cmp = InternalKeyComparator::new(Arc::new(BytewiseComparator));
+    let sorted = [
+        internal_key(b"a", 200, RecordKind::Value),
+        internal_key(b"a", 100, RecordKind::Deletion),
+        internal_key(b"a", 100, RecordKind::Value),
+        internal_key(b"ab", 300, RecordKind::Value),
+        internal_key(b"b", 1, RecordKind::Value),
+    ];
+    for pair in sorted.windows(2) {
+        assert_eq!(cmp.compare(&pair[0], &pair[1]), Ordering::Less);
+        assert_eq!(cmp.compare(&pair[1], &pair[0]), Ordering::Greater);
+    }
+    let seek = internal_key(b"a", 100, KIND_FOR_SEEK);
+    assert_eq!(cmp.compare(&seek, &sorted[1]), Ordering::Equal);
+    assert_eq!(cmp.compare(&seek, &sorted[2]), Ordering::Less);
+
+    let mut start = internal_key(b"foobar", 100, RecordKind::Value);
+    cmp.find_shortest_separator(
+        &mut start,
+        &internal_key(b"fz", 200, RecordKind::Value),
+    );
+    assert_eq!(start, internal_key(b"fp", MAX_SEQUENCE, KIND_FOR_SEEK));
+    // the same user key is left alone
+    let mut start = internal_key(b"foo", 100, RecordKind::Value);
+    cmp.find_shortest_separator(
+        &mut start,
+        &internal_key(b"foo", 99, RecordKind::Value),
+    );
+    assert_eq!(start, internal_key(b"foo", 100, RecordKind::Value));
+    let mut key = internal_key(b"foo", 100, RecordKind::Value);
+    cmp.find_short_successor(&mut key);
+    assert_eq!(key, internal_key(b"g", MAX_SEQUENCE, KIND_FOR_SEEK));
+}
@@ -0,0 +1,132 @@
+use crate::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Log,
+    Table,
+    // a file still being written, renamed into place once complete
+    Temp,
+    // the log of version edits
+    Manifest,
+    // names the manifest in use, its number is meaningless
+    Current,
+    // held locked by the process that has the database open,
+    // its number is meaningless too
+    Lock,
+}
+
+pub fn file_name(dir: &Path, number: u64, file_type: FileType) -> PathBuf {
+    match file_type {
+        FileType::Log => dir.join(format!("{number:06}.log")),
+        FileType::Table => dir.join(format!("{number:06}.sst")),
+        FileType::Temp => dir.join(format!("{number:06}.tmp")),
+        FileType::Manifest => dir.join(format!("MANIFEST-{number:06}")),
+        FileType::Current => dir.join("CURRENT"),
+        FileType::Lock => dir.join("LOCK"),
+    }
+}
+
+pub fn parse_file_name(name: &str) -> Option<(u64, FileType)> {
+    if name == "CURRENT" {
+        return Some((0, FileType::Current));
+    }
+    if name == "LOCK" {
+        return Some((0, FileType::Lock));
+    }
+    if let Some(number) = name.strip_prefix("MANIFEST-") {
+        return Some((number.parse().ok()?, FileType::Manifest));
+    }
+    let (number, ext) = name.split_once('.')?;
+    let file_type = match ext {
+        "log" => FileType::Log,
+        "sst" => FileType::Table,
+        "tmp" => FileType::Temp,
+        _ => return None,
+    };
+    Some((number.parse().ok()?, file_type))
+}
+
+// makes a rename in the directory durable
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+// points CURRENT at the manifest numbered manifest_number,
+// replacing it in one rename so it is never seen half written
+pub fn set_current_file(dir: &Path, manifest_number: u64) -> Result<(), Error> {
+    let manifest = file_name(dir, manifest_number, FileType::Manifest);
+    let name = manifest.file_name().unwrap().to_str().unwrap();
+    let tmp_path = file_name(dir, manifest_number, FileType::Temp);
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(format!("{name}\n").as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, file_name(dir, 0, FileType::Current))?;
+    sync_dir(dir)?;
+    Ok(())
+}
+
+// takes the lock of the database in dir, held until the file
+// returned is closed, Error::Locked if someone else holds it
+//
+// the lock is advisory and per open file, so it also keeps out
+// a second open of the same database within the process
+pub fn lock_dir(dir: &Path) -> Result<File, Error> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(file_name(dir, 0, FileType::Lock))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(fs::TryLockError::WouldBlock) => Err(Error::Locked),
+        Err(fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+// the number of the manifest CURRENT points at,
+// None if there is no CURRENT yet
+pub fn read_current_file(dir: &Path) -> Result<Option<u64>, Error> {
+    let contents =
+        match fs::read_to_string(file_name(dir, 0, FileType::Current)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+    match contents.strip_suffix('\n').and_then(parse_file_name) {
+        Some((number, FileType::Manifest)) => Ok(Some(number)),
+        _ => Err(Error::Corruption {
+            offset: None,
+            reason: "bad CURRENT file".to_string(),
+        }),
+    }
+}
+
+#[test]
+fn test_file_name() {
+    let dir = Path::new("/db");
+    for (number, file_type) in [
+        (1, FileType::Log),
+        (123, FileType::Table),
+        (4_567_890, FileType::Temp),
+        (12, FileType::Manifest),
+        (0, FileType::Current),
+        (0, FileType::Lock),
+    ] {
+        let path = file_name(dir, number, file_type);
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(parse_file_name(name), Some((number, file_type)));
+    }
+    for name in [
+        "",
+        "LOCKED",
+        "foo.log",
+        "000001.ldb",
+        "MANIFEST-",
+        "1.sst.x",
+    ] {
+        assert_eq!(parse_file_name(name), None);
+    }
+}
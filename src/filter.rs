@@ -0,0 +1,172 @@
+// a compact summary of the keys of a table, checked before reading
+// any of its data blocks so lookups of missing keys can skip it
+pub trait FilterPolicy: Send + Sync {
+    // stored along with the filter, a table's filter is only used
+    // when the database is opened with a policy of the same name
+    fn name(&self) -> &str;
+    fn create_filter(&self, keys: &[&[u8]]) -> Vec<u8>;
+    // false only if key was certainly not among the filter's keys
+    fn key_may_match(&self, key: &[u8], filter: &[u8]) -> bool;
+}
+
+// picks the part of a key prefix scans go by, tables built with one
+// also put the prefixes of their keys in their filters
+//
+// whatever starts with the prefix of a key must have that same prefix,
+// so scanning any key longer than a prefix finds it in the filters
+pub trait PrefixExtractor: Send + Sync {
+    // part of the name of the filters built with it
+    fn name(&self) -> &str;
+    // None for keys that have no prefix
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]>;
+}
+
+// the first len bytes of a key, keys shorter than that have no prefix
+pub struct FixedPrefix {
+    len: usize,
+    name: String,
+}
+
+impl FixedPrefix {
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            name: format!("reberu.FixedPrefix.{len}"),
+        }
+    }
+}
+
+impl PrefixExtractor for FixedPrefix {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.get(..self.len)
+    }
+}
+
+// murmur-like hash, same as leveldb's
+pub fn hash(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0xc6a4_a793;
+    let mut h = seed ^ (data.len() as u32).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        h = h.wrapping_add(u32::from_le_bytes(chunk.try_into().unwrap()));
+        h = h.wrapping_mul(M);
+        h ^= h >> 16;
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, byte) in rest.iter().enumerate() {
+            h = h.wrapping_add(u32::from(*byte) << (8 * i));
+        }
+        h = h.wrapping_mul(M);
+        h ^= h >> 24;
+    }
+    h
+}
+
+fn bloom_hash(key: &[u8]) -> u32 {
+    hash(key, 0xbc9f_1d34)
+}
+
+// a bloom filter with k probes derived from one hash by double hashing,
+// the filter is the bit array followed by k
+pub struct BloomFilterPolicy {
+    bits_per_key: usize,
+    k: usize,
+}
+
+impl BloomFilterPolicy {
+    // ~1% false positives at 10 bits per key
+    pub fn new(bits_per_key: usize) -> Self {
+        // ln(2) * bits per key minimizes the false positive rate
+        let k = (bits_per_key as f64 * 0.69) as usize;
+        Self {
+            bits_per_key,
+            k: k.clamp(1, 30),
+        }
+    }
+}
+
+impl FilterPolicy for BloomFilterPolicy {
+    fn name(&self) -> &str {
+        "reberu.BuiltinBloomFilter"
+    }
+
+    fn create_filter(&self, keys: &[&[u8]]) -> Vec<u8> {
+        // tiny filters have a high false positive rate anyway
+        let bits = (keys.len() * self.bits_per_key).max(64);
+        let bytes = bits.div_ceil(8);
+        let bits = bytes * 8;
+
+        let mut filter = vec![0; bytes + 1];
+        for key in keys {
+            let mut h = bloom_hash(key);
+            let delta = h.rotate_left(15);
+            for _ in 0..self.k {
+                let bit = h as usize % bits;
+                filter[bit / 8] |= 1 << (bit % 8);
+                h = h.wrapping_add(delta);
+            }
+        }
+        filter[bytes] = self.k as u8;
+        filter
+    }
+
+    fn key_may_match(&self, key: &[u8], filter: &[u8]) -> bool {
+        let Some((k, array)) = filter.split_last() else {
+            return false;
+        };
+        if array.is_empty() {
+            return false;
+        }
+        // reserved for other encodings, err on the side of reading
+        if *k > 30 {
+            return true;
+        }
+        let bits = array.len() * 8;
+        let mut h = bloom_hash(key);
+        let delta = h.rotate_left(15);
+        for _ in 0..*k {
+            let bit = h as usize % bits;
+            if array[bit / 8] & (1 << (bit % 8)) == 0 {
+                return false;
+            }
+            h = h.wrapping_add(delta);
+        }
+        true
+    }
+}
+
+#[test]
+fn test_hash() {
+    // values from leveldb's hash_test
+    assert_eq!(hash(b"", 0xbc9f_1d34), 0xbc9f_1d34);
+    assert_eq!(hash(&[0x62], 0xbc9f_1d34), 0xef13_45c4);
+    assert_eq!(hash(&[0xc3, 0x97], 0xbc9f_1d34), 0x5b66_3814);
+    assert_eq!(hash(&[0xe2, 0x99, 0xa5], 0xbc9f_1d34), 0x323c_078f);
+    assert_eq!(hash(&[0xe1, 0x80, 0xb9, 0x32], 0xbc9f_1d34), 0xed21_633a);
+}
+
+#[test]
+fn test_bloom_filter() {
+    let policy = BloomFilterPolicy::new(10);
+    assert!(!policy.key_may_match(b"hello", &policy.create_filter(&[])));
+
+    let keys = (0..10_000u32)
+        .map(|i| i.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let filter = policy
+        .create_filter(&keys.iter().map(Vec::as_slice).collect::<Vec<_>>());
+    for key in &keys {
+        assert!(policy.key_may_match(key, &filter));
+    }
+
+    let false_positives = (10_000..20_000u32)
+        .filter(|i| policy.key_may_match(&i.to_le_bytes(), &filter))
+        .count();
+    // 1% expected
+    assert!(false_positives < 200, "{false_positives} false positives");
+}
@@ -0,0 +1,77 @@
+use crate::Error;
+
+pub type Entry = (Vec<u8>, Vec<u8>);
+pub type Source = Box<dyn InternalIterator>;
+
+// a cursor over sorted key/value pairs, not valid until positioned,
+// it may be moved to another thread
+pub trait InternalIterator: Send {
+    fn valid(&self) -> bool;
+    fn key(&self) -> &[u8];
+    fn value(&self) -> &[u8];
+    fn seek_to_first(&mut self);
+    fn seek_to_last(&mut self);
+    // positions at the first entry with a key >= target
+    fn seek(&mut self, target: &[u8]);
+    fn next(&mut self);
+    fn prev(&mut self);
+    // the first error met while moving around, if any,
+    // the cursor stops being valid when it meets one
+    fn status(&mut self) -> Result<(), Error>;
+}
+
+impl<I: InternalIterator + ?Sized> InternalIterator for Box<I> {
+    fn valid(&self) -> bool {
+        (**self).valid()
+    }
+
+    fn key(&self) -> &[u8] {
+        (**self).key()
+    }
+
+    fn value(&self) -> &[u8] {
+        (**self).value()
+    }
+
+    fn seek_to_first(&mut self) {
+        (**self).seek_to_first()
+    }
+
+    fn seek_to_last(&mut self) {
+        (**self).seek_to_last()
+    }
+
+    fn seek(&mut self, target: &[u8]) {
+        (**self).seek(target)
+    }
+
+    fn next(&mut self) {
+        (**self).next()
+    }
+
+    fn prev(&mut self) {
+        (**self).prev()
+    }
+
+    fn status(&mut self) -> Result<(), Error> {
+        (**self).status()
+    }
+}
+
+// all the entries of iter from the first one on, copied
+pub fn entries(
+    mut iter: impl InternalIterator,
+) -> impl Iterator<Item = Result<Entry, Error>> {
+    iter.seek_to_first();
+    std::iter::from_fn(move || {
+        if let Err(e) = iter.status() {
+            return Some(Err(e));
+        }
+        if !iter.valid() {
+            return None;
+        }
+        let entry = (iter.key().to_vec(), iter.value().to_vec());
+        iter.next();
+        Some(Ok(entry))
+    })
+}
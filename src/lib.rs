@@ -1,154 +1,94 @@
 #[derive(Debug)]
 pub enum Error {
     KeyNotFound,
+    // the record at offset failed to decode or its checksum didn't match,
+    // no offset if the data isn't a record of some file
+    Corruption { offset: Option<u64>, reason: String },
+    // the options don't fit the database being opened
+    InvalidArgument(String),
+    // the database is already open, by this process or another
+    Locked,
+    Io(io::Error),
 }
 
-pub trait KV {
-    fn get(&self, key: &[u8]) -> Result<Vec<u8>, Error>;
-    fn has(&self, key: &[u8]) -> Result<bool, Error>;
-    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error>;
-    fn delete(&mut self, key: &[u8]) -> Result<(), Error>;
-}
-
-use indexmap::IndexMap;
-use std::cell::RefCell;
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufRead, Seek, SeekFrom, Write};
-
-pub struct Database {
-    file: RefCell<File>,
-    reader: RefCell<io::BufReader<File>>,
-    writer: io::BufWriter<File>,
-    idxs: IndexMap<Vec<u8>, u64>,
-}
-
-impl Database {
-    pub fn new(filename: &str, truncate: bool) -> io::Result<Self> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(truncate)
-            // .append(true)
-            .open(filename)?;
-
-        Ok(Self {
-            reader: io::BufReader::new(file.try_clone()?).into(),
-            writer: io::BufWriter::new(file.try_clone()?),
-            file: file.into(),
-            idxs: IndexMap::new(),
-        })
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
     }
 }
 
-impl KV for Database {
-    fn get(&self, key: &[u8]) -> Result<Vec<u8>, Error> {
-        let idx = match self.idxs.get(key) {
-            Some(idx) => idx,
-            None => return Err(Error::KeyNotFound),
-        };
-        self.file.borrow_mut().seek(SeekFrom::Start(*idx)).unwrap();
-        let mut value = vec![];
-        self.reader
-            .borrow_mut()
-            .read_until(b'\n', &mut value)
-            .unwrap();
-        // remove \n
-        value.pop();
-        Ok(value)
-    }
-    fn has(&self, key: &[u8]) -> Result<bool, Error> {
-        Ok(self.idxs.contains_key(key))
-    }
-    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
-        self.writer.write_all(key).unwrap();
-        self.writer.write_all(b",").unwrap();
-        self.writer.flush().unwrap();
-        self.idxs.insert(
-            key.to_vec(),
-            self.file.borrow_mut().stream_position().unwrap(),
-        );
-        self.writer.write_all(value).unwrap();
-        self.writer.write_all(b"\n").unwrap();
-        self.writer.flush().unwrap();
-        Ok(())
-    }
-    fn delete(&mut self, key: &[u8]) -> Result<(), Error> {
-        // O(n)
-        self.idxs.shift_remove(key);
-        Ok(())
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::KeyNotFound => write!(f, "key not found"),
+            Self::Corruption {
+                offset: Some(offset),
+                reason,
+            } => write!(f, "corruption at offset {offset}: {reason}"),
+            Self::Corruption {
+                offset: None,
+                reason,
+            } => write!(f, "corruption: {reason}"),
+            Self::InvalidArgument(reason) => {
+                write!(f, "invalid argument: {reason}")
+            }
+            Self::Locked => write!(f, "database is locked"),
+            Self::Io(e) => write!(f, "io error: {e}"),
+        }
     }
 }
 
-#[test]
-fn test_full() {
-    let mut db = Database::new("/tmp/test_full", true).unwrap();
-
-    assert!(!db.has(b"abc").unwrap());
-
-    db.put(b"abc", b"xyz").unwrap();
-
-    assert!(db.has(b"abc").unwrap());
-    assert_eq!(db.get(b"abc").unwrap(), b"xyz");
-
-    db.delete(b"abc").unwrap();
-
-    assert!(!db.has(b"abc").unwrap());
-}
-
-pub struct DBIterator {
-    reader: RefCell<io::BufReader<File>>,
-    idxs: indexmap::map::IntoIter<Vec<u8>, u64>,
-}
-
-impl IntoIterator for Database {
-    type Item = (Vec<u8>, Vec<u8>);
-    type IntoIter = DBIterator;
-    fn into_iter(self) -> Self::IntoIter {
-        DBIterator {
-            reader: self.reader,
-            idxs: self.idxs.into_iter(),
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
         }
     }
 }
 
-impl Iterator for DBIterator {
-    type Item = (Vec<u8>, Vec<u8>);
-    // very similar code to Database::get()
-    // perhaps we could abstract
-    fn next(&mut self) -> Option<Self::Item> {
-        let (key, offset) = self.idxs.next()?;
-        self.reader
-            .borrow_mut()
-            .seek(SeekFrom::Start(offset))
-            .unwrap();
-        let mut value = vec![];
-        self.reader
-            .borrow_mut()
-            .read_until(b'\n', &mut value)
-            .unwrap();
-        // remove \n
-        value.pop();
-        Some((key, value))
-    }
+pub trait KV {
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>, Error>;
+    fn has(&self, key: &[u8]) -> Result<bool, Error>;
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error>;
+    fn delete(&self, key: &[u8]) -> Result<(), Error>;
 }
 
-#[test]
-fn test_iter() {
-    let mut db = Database::new("/tmp/test_iter", true).unwrap();
-    let numbers = vec!["one", "two", "three"];
-
-    for (i, n) in numbers.iter().enumerate() {
-        db.put((i + 1).to_string().as_bytes(), n.as_bytes())
-            .unwrap();
-    }
-
-    assert_eq!(
-        db.into_iter().collect::<Vec<_>>(),
-        vec![
-            (b"1".to_vec(), b"one".to_vec()),
-            (b"2".to_vec(), b"two".to_vec()),
-            (b"3".to_vec(), b"three".to_vec())
-        ]
-    );
-}
+mod block;
+mod cache;
+mod coding;
+mod comparator;
+mod compress;
+mod crc32c;
+mod db;
+mod db_iter;
+mod dbformat;
+mod filename;
+mod filter;
+mod iterator;
+mod log;
+mod memtable;
+mod merge;
+mod options;
+mod range_del;
+mod snapshot;
+mod table;
+mod table_cache;
+mod version;
+mod version_edit;
+mod write_batch;
+
+pub use cache::CacheStats;
+pub use comparator::{BytewiseComparator, Comparator};
+pub use compress::{Compressor, LzCompressor};
+pub use db::Database;
+pub use db_iter::{DBIterator, Entries};
+pub use filter::{
+    BloomFilterPolicy, FilterPolicy, FixedPrefix, PrefixExtractor,
+};
+pub use options::{Options, ReadOptions, WriteOptions};
+pub use snapshot::Snapshot;
+pub use write_batch::{BatchRecord, WriteBatch};
+
+use std::fmt;
+use std::io;
@@ -0,0 +1,172 @@
+use crate::coding;
+use crate::crc32c;
+use crate::Error;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+// every file starts with the magic followed by the format version
+const MAGIC: &[u8; 4] = b"RBRU";
+const FORMAT_VERSION: u8 = 5;
+pub const HEADER_LEN: u64 = MAGIC.len() as u64 + 1;
+
+pub fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION])
+}
+
+pub fn read_header(reader: &mut impl Read) -> Result<(), Error> {
+    let mut header = [0; HEADER_LEN as usize];
+    reader.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(Error::Corruption {
+            offset: Some(0),
+            reason: "not a reberu file".to_string(),
+        });
+    }
+    if header[MAGIC.len()] != FORMAT_VERSION {
+        return Err(Error::Corruption {
+            offset: Some(MAGIC.len() as u64),
+            reason: format!(
+                "unsupported format version {}",
+                header[MAGIC.len()]
+            ),
+        });
+    }
+    Ok(())
+}
+
+// record layout:
+// crc32c | varint payload len | payload
+// the checksum covers everything that follows it
+pub fn encode_record(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(record_len(payload) as usize);
+    record.extend_from_slice(&[0; 4]);
+    coding::put_varint(&mut record, payload.len() as u64);
+    record.extend_from_slice(payload);
+    let crc = crc32c::value(&record[4..]);
+    record[..4].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+// None if the log ends partway through the record, which is what
+// a crash in the middle of appending it leaves behind
+pub fn read_record(
+    reader: &mut impl Read,
+    offset: u64,
+) -> Result<Option<Vec<u8>>, Error> {
+    let corruption = |reason: &str| Error::Corruption {
+        offset: Some(offset),
+        reason: reason.to_string(),
+    };
+    let mut crc = [0; 4];
+    match reader.read_exact(&mut crc) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let len = match coding::read_varint(reader) {
+        Ok(len) => len,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return Err(corruption("bad length"))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // the length may be garbage, only allocate what is actually there
+    let mut payload = vec![];
+    reader.take(len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != len {
+        return Ok(None);
+    }
+
+    let mut header = vec![];
+    coding::put_varint(&mut header, len);
+    let expected = crc32c::extend(crc32c::value(&header), &payload);
+    if expected.to_le_bytes() != crc {
+        return Err(corruption("checksum mismatch"));
+    }
+    Ok(Some(payload))
+}
+
+pub fn record_len(payload: &[u8]) -> u64 {
+    (4 + coding::varint_len(payload.len() as u64) + payload.len()) as u64
+}
+
+// the write-ahead log, every write batch is appended here
+// before it reaches the memtable, the manifest is one of these
+// too, holding version edits
+pub struct Writer {
+    writer: io::BufWriter<File>,
+}
+
+impl Writer {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        let mut writer = io::BufWriter::new(File::create(path)?);
+        write_header(&mut writer)?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    pub fn add_record(&mut self, payload: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(&encode_record(payload))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // makes the records added so far durable
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.writer.get_ref().sync_all()?;
+        Ok(())
+    }
+
+    // a handle on the file to sync it with while records keep
+    // being added, every record added is already handed to the file
+    pub fn file(&self) -> Result<File, Error> {
+        Ok(self.writer.get_ref().try_clone()?)
+    }
+}
+
+// calls f for every record of the log at path, in order,
+// stopping at the first error it returns, a corruption without an
+// offset gets the record's
+//
+// a record or header cut short at the very end is a torn write,
+// replay stops there as if the log ended right before it, a record
+// that doesn't check out anywhere is returned as the corruption it is
+//
+// true if every record of the log was replayed
+pub fn replay(
+    path: &Path,
+    mut f: impl FnMut(Vec<u8>) -> Result<(), Error>,
+) -> Result<bool, Error> {
+    let mut reader = io::BufReader::new(File::open(path)?);
+    // a crash right after the file was made may leave less than
+    // the header, which is no different from a torn record
+    let mut header = vec![];
+    (&mut reader).take(HEADER_LEN).read_to_end(&mut header)?;
+    if header.len() < HEADER_LEN as usize {
+        return Ok(header.is_empty());
+    }
+    read_header(&mut header.as_slice())?;
+    let mut offset = HEADER_LEN;
+    while !reader.fill_buf()?.is_empty() {
+        let payload = match read_record(&mut reader, offset) {
+            Ok(Some(payload)) => payload,
+            Ok(None) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let len = record_len(&payload);
+        f(payload).map_err(|e| match e {
+            Error::Corruption {
+                offset: None,
+                reason,
+            } => Error::Corruption {
+                offset: Some(offset),
+                reason,
+            },
+            e => e,
+        })?;
+        offset += len;
+    }
+    Ok(true)
+}
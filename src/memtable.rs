@@ -0,0 +1,283 @@
+use crate::comparator::Comparator;
+use crate::dbformat::{
+    internal_key, parse_internal_key, InternalKeyComparator, RecordKind,
+    KIND_FOR_SEEK,
+};
+use crate::iterator::InternalIterator;
+use crate::range_del::{self, RangeTombstone};
+use crate::Error;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{Arc, RwLock};
+
+// an internal key ordered by the comparator it carries
+struct MemKey {
+    key: Vec<u8>,
+    comparator: Arc<InternalKeyComparator>,
+}
+
+impl Ord for MemKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.comparator.compare(&self.key, &other.key)
+    }
+}
+
+impl PartialOrd for MemKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MemKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MemKey {}
+
+// sorted in-memory buffer of the latest writes, every version of
+// a key is kept under its own internal key
+//
+// shared between the writer and whoever is reading it, so it can
+// be added to through a shared reference
+pub struct MemTable {
+    comparator: Arc<InternalKeyComparator>,
+    map: RwLock<BTreeMap<MemKey, Vec<u8>>>,
+    range_tombstones: RwLock<Vec<RangeTombstone>>,
+    // bytes of keys and values added so far
+    size: AtomicUsize,
+}
+
+impl MemTable {
+    pub fn new(comparator: Arc<InternalKeyComparator>) -> Self {
+        Self {
+            comparator,
+            map: RwLock::new(BTreeMap::new()),
+            range_tombstones: RwLock::new(vec![]),
+            size: AtomicUsize::new(0),
+        }
+    }
+
+    fn mem_key(&self, key: Vec<u8>) -> MemKey {
+        MemKey {
+            key,
+            comparator: self.comparator.clone(),
+        }
+    }
+
+    pub fn add(
+        &self,
+        sequence: u64,
+        kind: RecordKind,
+        key: &[u8],
+        value: &[u8],
+    ) {
+        let key = internal_key(key, sequence, kind);
+        self.size
+            .fetch_add(key.len() + value.len(), atomic::Ordering::Relaxed);
+        let key = self.mem_key(key);
+        self.map.write().unwrap().insert(key, value.to_vec());
+    }
+
+    pub fn add_range_deletion(&self, sequence: u64, start: &[u8], end: &[u8]) {
+        self.size
+            .fetch_add(start.len() + end.len(), atomic::Ordering::Relaxed);
+        self.range_tombstones.write().unwrap().push(RangeTombstone {
+            sequence,
+            start: start.to_vec(),
+            end: end.to_vec(),
+        });
+    }
+
+    pub fn range_tombstones(&self) -> Vec<RangeTombstone> {
+        self.range_tombstones.read().unwrap().clone()
+    }
+
+    // the newest version of key no newer than sequence,
+    // None if the memtable knows nothing about it,
+    // Some(None) if it was deleted
+    pub fn get(&self, key: &[u8], sequence: u64) -> Option<Option<Vec<u8>>> {
+        let user_comparator = self.comparator.user_comparator();
+        let covering = range_del::newest_covering(
+            user_comparator.as_ref(),
+            self.range_tombstones.read().unwrap().iter(),
+            key,
+            sequence,
+        );
+        let seek = self.mem_key(internal_key(key, sequence, KIND_FOR_SEEK));
+        let map = self.map.read().unwrap();
+        let found = map.range(seek..).next().and_then(|(found, value)| {
+            let (user_key, found_sequence, kind) =
+                parse_internal_key(&found.key)?;
+            (user_comparator.compare(user_key, key) == Ordering::Equal)
+                .then_some((found_sequence, kind, value))
+        });
+        match found {
+            // a range deletion newer than it hides it
+            Some((found_sequence, RecordKind::Value, value))
+                if covering
+                    .is_none_or(|covering| covering < found_sequence) =>
+            {
+                Some(Some(value.clone()))
+            }
+            Some(_) => Some(None),
+            None => covering.map(|_| None),
+        }
+    }
+
+    pub fn approximate_size(&self) -> usize {
+        self.size.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.read().unwrap().is_empty()
+            && self.range_tombstones.read().unwrap().is_empty()
+    }
+
+    // a cursor over the entries by internal key, each one copied
+    // out as it gets to it so writes can keep going meanwhile
+    pub fn iter(self: &Arc<Self>) -> MemTableIterator {
+        MemTableIterator {
+            mem: self.clone(),
+            current: None,
+        }
+    }
+}
+
+pub struct MemTableIterator {
+    mem: Arc<MemTable>,
+    // a copy of the entry the cursor is at, the entries around it
+    // are looked up relative to its key
+    current: Option<(MemKey, Vec<u8>)>,
+}
+
+impl MemTableIterator {
+    fn set_current(&mut self, entry: Option<(&MemKey, &Vec<u8>)>) {
+        self.current = entry.map(|(key, value)| {
+            (self.mem.mem_key(key.key.clone()), value.clone())
+        });
+    }
+
+    fn current_key(&self) -> &MemKey {
+        &self.current.as_ref().unwrap().0
+    }
+}
+
+impl InternalIterator for MemTableIterator {
+    fn valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    fn key(&self) -> &[u8] {
+        &self.current_key().key
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.current.as_ref().unwrap().1
+    }
+
+    fn seek_to_first(&mut self) {
+        let mem = self.mem.clone();
+        let map = mem.map.read().unwrap();
+        self.set_current(map.iter().next());
+    }
+
+    fn seek_to_last(&mut self) {
+        let mem = self.mem.clone();
+        let map = mem.map.read().unwrap();
+        self.set_current(map.iter().next_back());
+    }
+
+    fn seek(&mut self, target: &[u8]) {
+        let mem = self.mem.clone();
+        let target = mem.mem_key(target.to_vec());
+        let map = mem.map.read().unwrap();
+        self.set_current(map.range(target..).next());
+    }
+
+    fn next(&mut self) {
+        let mem = self.mem.clone();
+        let map = mem.map.read().unwrap();
+        let after = (Bound::Excluded(self.current_key()), Bound::Unbounded);
+        let entry = map.range(after).next();
+        self.set_current(entry);
+    }
+
+    fn prev(&mut self) {
+        let mem = self.mem.clone();
+        let map = mem.map.read().unwrap();
+        let entry = map.range(..self.current_key()).next_back();
+        self.set_current(entry);
+    }
+
+    fn status(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_memtable() {
+    use crate::comparator::BytewiseComparator;
+
+    let comparator = InternalKeyComparator::new(Arc::new(BytewiseComparator));
+    let mem = Arc::new(MemTable::new(Arc::new(comparator)));
+    mem.add(1, RecordKind::Value, b"b", b"2");
+    mem.add(2, RecordKind::Value, b"a", b"1");
+    mem.add(3, RecordKind::Deletion, b"c", b"");
+    mem.add(4, RecordKind::Value, b"a", b"3");
+
+    assert_eq!(mem.get(b"a", 4), Some(Some(b"3".to_vec())));
+    assert_eq!(mem.get(b"a", 3), Some(Some(b"1".to_vec())));
+    assert_eq!(mem.get(b"a", 1), None);
+    assert_eq!(mem.get(b"c", 4), Some(None));
+    assert_eq!(mem.get(b"d", 4), None);
+
+    // hides what came before it, not what came after
+    mem.add_range_deletion(6, b"a", b"c");
+    mem.add(7, RecordKind::Value, b"b", b"5");
+    assert_eq!(mem.get(b"a", 7), Some(None));
+    assert_eq!(mem.get(b"a", 5), Some(Some(b"3".to_vec())));
+    assert_eq!(mem.get(b"b", 7), Some(Some(b"5".to_vec())));
+    assert_eq!(mem.get(b"b", 6), Some(None));
+    assert_eq!(mem.get(b"ab", 7), Some(None));
+    assert_eq!(mem.get(b"c", 7), Some(None));
+    assert_eq!(mem.get(b"d", 7), None);
+
+    let mut iter = mem.iter();
+    iter.seek_to_first();
+    assert_eq!(iter.key(), internal_key(b"a", 4, RecordKind::Value));
+    assert_eq!(iter.value(), b"3");
+    // added after the iterator was made, but ahead of it
+    mem.add(5, RecordKind::Value, b"b", b"4");
+    let mut keys = vec![];
+    while iter.valid() {
+        keys.push(
+            parse_internal_key(iter.key()).map(|(k, s, _)| (k.to_vec(), s)),
+        );
+        iter.next();
+    }
+    assert_eq!(
+        keys,
+        [
+            Some((b"a".to_vec(), 4)),
+            Some((b"a".to_vec(), 2)),
+            Some((b"b".to_vec(), 7)),
+            Some((b"b".to_vec(), 5)),
+            Some((b"b".to_vec(), 1)),
+            Some((b"c".to_vec(), 3)),
+        ]
+    );
+
+    iter.seek(&internal_key(b"b", 4, KIND_FOR_SEEK));
+    assert_eq!(iter.value(), b"2");
+    iter.prev();
+    assert_eq!(iter.value(), b"4");
+    iter.seek_to_last();
+    assert_eq!(iter.key(), internal_key(b"c", 3, RecordKind::Deletion));
+    iter.seek_to_first();
+    iter.prev();
+    assert!(!iter.valid());
+}
@@ -0,0 +1,220 @@
+use crate::comparator::Comparator;
+use crate::iterator::{InternalIterator, Source};
+use crate::Error;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Reverse,
+}
+
+// merges sorted sources into one sorted cursor, every entry of
+// every source comes out
+//
+// no key may be in more than one source, which internal keys never
+// are, going forward every source is at or after the current entry,
+// going backwards every source is at or before it, so changing
+// direction has to reposition all of them
+pub struct MergingIterator {
+    comparator: Arc<dyn Comparator>,
+    sources: Vec<Source>,
+    // the source holding the current entry
+    current: Option<usize>,
+    direction: Direction,
+}
+
+impl MergingIterator {
+    pub fn new(comparator: Arc<dyn Comparator>, sources: Vec<Source>) -> Self {
+        Self {
+            comparator,
+            sources,
+            current: None,
+            direction: Direction::Forward,
+        }
+    }
+
+    fn find_smallest(&mut self) {
+        let mut smallest: Option<usize> = None;
+        for (i, source) in self.sources.iter().enumerate() {
+            if source.valid()
+                && smallest.is_none_or(|smallest| {
+                    let smallest = self.sources[smallest].key();
+                    self.comparator.compare(source.key(), smallest)
+                        == Ordering::Less
+                })
+            {
+                smallest = Some(i);
+            }
+        }
+        self.current = smallest;
+    }
+
+    fn find_largest(&mut self) {
+        let mut largest: Option<usize> = None;
+        for (i, source) in self.sources.iter().enumerate().rev() {
+            if source.valid()
+                && largest.is_none_or(|largest| {
+                    let largest = self.sources[largest].key();
+                    self.comparator.compare(source.key(), largest)
+                        == Ordering::Greater
+                })
+            {
+                largest = Some(i);
+            }
+        }
+        self.current = largest;
+    }
+}
+
+impl InternalIterator for MergingIterator {
+    fn valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    fn key(&self) -> &[u8] {
+        self.sources[self.current.unwrap()].key()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.sources[self.current.unwrap()].value()
+    }
+
+    fn seek_to_first(&mut self) {
+        for source in &mut self.sources {
+            source.seek_to_first();
+        }
+        self.direction = Direction::Forward;
+        self.find_smallest();
+    }
+
+    fn seek_to_last(&mut self) {
+        for source in &mut self.sources {
+            source.seek_to_last();
+        }
+        self.direction = Direction::Reverse;
+        self.find_largest();
+    }
+
+    fn seek(&mut self, target: &[u8]) {
+        for source in &mut self.sources {
+            source.seek(target);
+        }
+        self.direction = Direction::Forward;
+        self.find_smallest();
+    }
+
+    fn next(&mut self) {
+        let current = self.current.unwrap();
+        if self.direction == Direction::Reverse {
+            // move the other sources past the current key
+            let key = self.key().to_vec();
+            for (i, source) in self.sources.iter_mut().enumerate() {
+                if i == current {
+                    continue;
+                }
+                source.seek(&key);
+                if source.valid()
+                    && self.comparator.compare(&key, source.key())
+                        == Ordering::Equal
+                {
+                    source.next();
+                }
+            }
+            self.direction = Direction::Forward;
+        }
+        self.sources[current].next();
+        self.find_smallest();
+    }
+
+    fn prev(&mut self) {
+        let current = self.current.unwrap();
+        if self.direction == Direction::Forward {
+            // move the other sources before the current key
+            let key = self.key().to_vec();
+            for (i, source) in self.sources.iter_mut().enumerate() {
+                if i == current {
+                    continue;
+                }
+                source.seek(&key);
+                if source.valid() {
+                    source.prev();
+                } else {
+                    source.seek_to_last();
+                }
+            }
+            self.direction = Direction::Reverse;
+        }
+        self.sources[current].prev();
+        self.find_largest();
+    }
+
+    fn status(&mut self) -> Result<(), Error> {
+        for source in &mut self.sources {
+            source.status()?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_merge() {
+    use crate::comparator::BytewiseComparator;
+    use crate::iterator::entries;
+    use crate::table::{Table, TableBuilder};
+    use crate::{Options, ReadOptions};
+    use std::fs::File;
+
+    let table = |path: &str, entries: &[(&[u8], &[u8])]| -> Source {
+        let comparator = Arc::new(BytewiseComparator);
+        let options = Options::default();
+        let file = File::create(path).unwrap();
+        let mut builder = TableBuilder::new(file, &options, comparator.clone());
+        for (key, value) in entries {
+            builder.add(key, value).unwrap();
+        }
+        let size = builder.finish().unwrap();
+        let file = File::open(path).unwrap();
+        let table = Table::open(file, size, &options, comparator).unwrap();
+        Box::new(Arc::new(table).iter(&ReadOptions::default()))
+    };
+    let first = table("/tmp/test_merge_first", &[(b"b", b"2"), (b"e", b"5")]);
+    let second = table(
+        "/tmp/test_merge_second",
+        &[(b"a", b"1"), (b"c", b"3"), (b"d", b"4")],
+    );
+    let mut merged =
+        MergingIterator::new(Arc::new(BytewiseComparator), vec![first, second]);
+
+    merged.seek(b"bb");
+    assert_eq!(merged.key(), b"c");
+    merged.next();
+    assert_eq!(merged.key(), b"d");
+    // turning around
+    merged.prev();
+    assert_eq!(merged.key(), b"c");
+    merged.prev();
+    assert_eq!(merged.key(), b"b");
+    merged.next();
+    assert_eq!(merged.key(), b"c");
+    merged.seek_to_first();
+    merged.prev();
+    assert!(!merged.valid());
+    merged.seek_to_last();
+    assert_eq!(merged.key(), b"e");
+    merged.prev();
+    assert_eq!(merged.key(), b"d");
+
+    let entry = |key: &[u8], value: &[u8]| (key.to_vec(), value.to_vec());
+    assert_eq!(
+        entries(merged).collect::<Result<Vec<_>, _>>().unwrap(),
+        [
+            entry(b"a", b"1"),
+            entry(b"b", b"2"),
+            entry(b"c", b"3"),
+            entry(b"d", b"4"),
+            entry(b"e", b"5"),
+        ]
+    );
+}
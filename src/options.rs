@@ -0,0 +1,395 @@
+use crate::comparator::{BytewiseComparator, Comparator};
+use crate::compress::{Compressor, LzCompressor};
+use crate::filter::{BloomFilterPolicy, FilterPolicy, PrefixExtractor};
+use crate::snapshot::Snapshot;
+use crate::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+// file descriptors kept for the logs, the manifest and such,
+// the rest of max_open_files goes to tables
+pub const NUM_NON_TABLE_FILES: usize = 10;
+
+// every field has a setter of the same name, so options can be
+// built up from the defaults in one go:
+// Options::default().create_if_missing(true).block_size(16 << 10)
+#[derive(Clone)]
+pub struct Options {
+    // opening a database that isn't there creates it instead of
+    // failing
+    pub create_if_missing: bool,
+    // opening a database that is already there fails
+    pub error_if_exists: bool,
+    // every table is opened up front to check it
+    pub paranoid_checks: bool,
+    // with it set, the log is synced every interval, so a crash of
+    // the machine loses at most that much of the writes not made
+    // with WriteOptions::sync, without any of them waiting on a sync
+    pub sync_interval: Option<Duration>,
+    // bytes the memtable may hold before it is written out to a table
+    pub write_buffer_size: usize,
+    // tables kept open at once is this minus NUM_NON_TABLE_FILES
+    pub max_open_files: usize,
+    // bytes of entries in a data block, before compression
+    pub block_size: usize,
+    // keys between restart points of a data block, a restart point
+    // holds a key whole instead of sharing a prefix with the last one
+    pub block_restart_interval: usize,
+    // compresses the data blocks of new tables, None stores them as is
+    pub compressor: Option<Arc<dyn Compressor>>,
+    // a block compression doesn't shrink by at least this percentage
+    // is stored as is, not worth decompressing on every read
+    pub min_compression_savings: u8,
+    // bytes of data blocks kept in memory across every table,
+    // 0 reads every block from its file
+    pub block_cache_capacity: usize,
+    // level 0 is compacted once it has this many tables
+    pub level0_compaction_trigger: usize,
+    // writes wait for the background thread once level 0
+    // has this many tables
+    pub level0_stop_writes_trigger: usize,
+    // bytes of tables level 1 may hold before it is compacted,
+    // every level after it may hold ten times the one before
+    pub max_bytes_for_level_base: u64,
+    // compactions start a new table once one grows past this
+    pub max_file_size: u64,
+    // builds a filter for every new table, lookups of keys the filter
+    // rules out never touch the table's data blocks
+    pub filter_policy: Option<Arc<dyn FilterPolicy>>,
+    // the filters also hold the prefixes it picks out of the keys,
+    // so prefix scans skip the tables without any keys of the prefix
+    pub prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    // the order keys are kept in, a database only ever opens with
+    // a comparator of the same name as the one it was created with
+    pub comparator: Arc<dyn Comparator>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            create_if_missing: false,
+            error_if_exists: false,
+            paranoid_checks: false,
+            sync_interval: None,
+            write_buffer_size: 4 << 20,
+            max_open_files: 1000,
+            block_size: 4096,
+            block_restart_interval: 16,
+            compressor: Some(Arc::new(LzCompressor)),
+            min_compression_savings: 12,
+            block_cache_capacity: 8 << 20,
+            level0_compaction_trigger: 4,
+            level0_stop_writes_trigger: 12,
+            max_bytes_for_level_base: 10 << 20,
+            max_file_size: 2 << 20,
+            filter_policy: Some(Arc::new(BloomFilterPolicy::new(10))),
+            prefix_extractor: None,
+            comparator: Arc::new(BytewiseComparator),
+        }
+    }
+}
+
+impl Options {
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.create_if_missing = create_if_missing;
+        self
+    }
+
+    pub fn error_if_exists(mut self, error_if_exists: bool) -> Self {
+        self.error_if_exists = error_if_exists;
+        self
+    }
+
+    pub fn paranoid_checks(mut self, paranoid_checks: bool) -> Self {
+        self.paranoid_checks = paranoid_checks;
+        self
+    }
+
+    pub fn sync_interval(mut self, sync_interval: Option<Duration>) -> Self {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    pub fn write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = write_buffer_size;
+        self
+    }
+
+    pub fn max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files;
+        self
+    }
+
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn block_restart_interval(mut self, interval: usize) -> Self {
+        self.block_restart_interval = interval;
+        self
+    }
+
+    pub fn compressor(
+        mut self,
+        compressor: Option<Arc<dyn Compressor>>,
+    ) -> Self {
+        self.compressor = compressor;
+        self
+    }
+
+    pub fn min_compression_savings(mut self, percentage: u8) -> Self {
+        self.min_compression_savings = percentage;
+        self
+    }
+
+    pub fn block_cache_capacity(mut self, capacity: usize) -> Self {
+        self.block_cache_capacity = capacity;
+        self
+    }
+
+    pub fn level0_compaction_trigger(mut self, trigger: usize) -> Self {
+        self.level0_compaction_trigger = trigger;
+        self
+    }
+
+    pub fn level0_stop_writes_trigger(mut self, trigger: usize) -> Self {
+        self.level0_stop_writes_trigger = trigger;
+        self
+    }
+
+    pub fn max_bytes_for_level_base(mut self, bytes: u64) -> Self {
+        self.max_bytes_for_level_base = bytes;
+        self
+    }
+
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    pub fn filter_policy(
+        mut self,
+        filter_policy: Option<Arc<dyn FilterPolicy>>,
+    ) -> Self {
+        self.filter_policy = filter_policy;
+        self
+    }
+
+    pub fn prefix_extractor(
+        mut self,
+        prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    ) -> Self {
+        self.prefix_extractor = prefix_extractor;
+        self
+    }
+
+    pub fn comparator(mut self, comparator: Arc<dyn Comparator>) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    // the first setting that can't work, if any
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| Err(Error::InvalidArgument(reason.into()));
+        if self.sync_interval == Some(Duration::ZERO) {
+            return invalid("sync_interval must be positive");
+        }
+        if self.write_buffer_size == 0 {
+            return invalid("write_buffer_size must be positive");
+        }
+        if self.max_open_files <= NUM_NON_TABLE_FILES {
+            return invalid("max_open_files leaves no room for tables");
+        }
+        if self.block_size == 0 {
+            return invalid("block_size must be positive");
+        }
+        if self.block_restart_interval == 0 {
+            return invalid("block_restart_interval must be positive");
+        }
+        if self.compressor.as_ref().is_some_and(|c| c.id() == 0) {
+            return invalid("compressor id 0 is for uncompressed blocks");
+        }
+        if self.compressor.as_ref().is_some_and(|c| {
+            c.id() == LzCompressor::ID && c.name() != LzCompressor.name()
+        }) {
+            return invalid("compressor id 1 is for the built-in compressor");
+        }
+        if self.min_compression_savings > 100 {
+            return invalid("min_compression_savings is over 100");
+        }
+        if self.level0_compaction_trigger == 0 {
+            return invalid("level0_compaction_trigger must be positive");
+        }
+        if self.level0_stop_writes_trigger < self.level0_compaction_trigger {
+            return invalid(
+                "level0_stop_writes_trigger is below \
+                 level0_compaction_trigger",
+            );
+        }
+        if self.max_bytes_for_level_base == 0 || self.max_file_size == 0 {
+            return invalid("level and file sizes must be positive");
+        }
+        Ok(())
+    }
+}
+
+// how a single write is made
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions {
+    // the log is synced before the write returns, so it survives
+    // a crash of the machine and not just of the process
+    pub sync: bool,
+}
+
+impl WriteOptions {
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+}
+
+// how a single read, or an iterator, reads
+#[derive(Clone, Copy)]
+pub struct ReadOptions<'a> {
+    // the blocks read from tables are checked against their checksums,
+    // on by default, turning it off trades catching a corrupted block
+    // for not having to checksum every block read
+    pub verify_checksums: bool,
+    // the blocks read go into the block cache, big scans over data
+    // that won't be read again soon should leave it alone, blocks
+    // not checked against their checksums never go in
+    pub fill_cache: bool,
+    // reads see the database as of it, or as it is when they start
+    // if there is none
+    pub snapshot: Option<&'a Snapshot>,
+}
+
+impl Default for ReadOptions<'_> {
+    fn default() -> Self {
+        Self {
+            verify_checksums: true,
+            fill_cache: true,
+            snapshot: None,
+        }
+    }
+}
+
+impl<'a> ReadOptions<'a> {
+    pub fn verify_checksums(mut self, verify_checksums: bool) -> Self {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+
+    pub fn fill_cache(mut self, fill_cache: bool) -> Self {
+        self.fill_cache = fill_cache;
+        self
+    }
+
+    pub fn snapshot(mut self, snapshot: Option<&'a Snapshot>) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    // the same without the snapshot, for the parts of a read that
+    // outlive it and only care about how blocks are read
+    pub(crate) fn without_snapshot(&self) -> ReadOptions<'static> {
+        ReadOptions {
+            verify_checksums: self.verify_checksums,
+            fill_cache: self.fill_cache,
+            snapshot: None,
+        }
+    }
+}
+
+impl fmt::Debug for ReadOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReadOptions")
+            .field("verify_checksums", &self.verify_checksums)
+            .field("fill_cache", &self.fill_cache)
+            .field("snapshot", &self.snapshot.map(Snapshot::sequence))
+            .finish()
+    }
+}
+
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Options")
+            .field("create_if_missing", &self.create_if_missing)
+            .field("error_if_exists", &self.error_if_exists)
+            .field("paranoid_checks", &self.paranoid_checks)
+            .field("sync_interval", &self.sync_interval)
+            .field("write_buffer_size", &self.write_buffer_size)
+            .field("max_open_files", &self.max_open_files)
+            .field("block_size", &self.block_size)
+            .field("block_restart_interval", &self.block_restart_interval)
+            .field(
+                "compressor",
+                &self.compressor.as_ref().map(|compressor| compressor.name()),
+            )
+            .field("min_compression_savings", &self.min_compression_savings)
+            .field("block_cache_capacity", &self.block_cache_capacity)
+            .field("level0_compaction_trigger", &self.level0_compaction_trigger)
+            .field(
+                "level0_stop_writes_trigger",
+                &self.level0_stop_writes_trigger,
+            )
+            .field("max_bytes_for_level_base", &self.max_bytes_for_level_base)
+            .field("max_file_size", &self.max_file_size)
+            .field(
+                "filter_policy",
+                &self.filter_policy.as_ref().map(|policy| policy.name()),
+            )
+            .field(
+                "prefix_extractor",
+                &self
+                    .prefix_extractor
+                    .as_ref()
+                    .map(|extractor| extractor.name()),
+            )
+            .field("comparator", &self.comparator.name())
+            .finish()
+    }
+}
+
+#[test]
+fn test_options() {
+    let options = Options::default()
+        .create_if_missing(true)
+        .block_size(16 << 10)
+        .filter_policy(None);
+    assert!(options.create_if_missing);
+    assert_eq!(options.block_size, 16 << 10);
+    assert!(options.filter_policy.is_none());
+    options.validate().unwrap();
+
+    // passes blocks through, under the id of the built-in one
+    struct Passthrough;
+    impl Compressor for Passthrough {
+        fn name(&self) -> &str {
+            "test.Passthrough"
+        }
+        fn id(&self) -> u8 {
+            LzCompressor::ID
+        }
+        fn compress(&self, input: &[u8]) -> Vec<u8> {
+            input.to_vec()
+        }
+        fn decompress(&self, input: &[u8]) -> Option<Vec<u8>> {
+            Some(input.to_vec())
+        }
+    }
+
+    for options in [
+        Options::default().sync_interval(Some(Duration::ZERO)),
+        Options::default().write_buffer_size(0),
+        Options::default().max_open_files(NUM_NON_TABLE_FILES),
+        Options::default().block_restart_interval(0),
+        Options::default().compressor(Some(Arc::new(Passthrough))),
+        Options::default().min_compression_savings(101),
+        Options::default().level0_stop_writes_trigger(2),
+    ] {
+        assert!(matches!(options.validate(), Err(Error::InvalidArgument(_))));
+    }
+}
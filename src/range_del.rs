@@ -0,0 +1,193 @@
+use crate::comparator::Comparator;
+use std::cmp::Ordering;
+
+// deletes every version older than it of the user keys in [start, end)
+//
+// the ones in the memtable are kept apart from its entries, once
+// written out they go along with the table whose key range holds
+// them, in its metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeTombstone {
+    pub sequence: u64,
+    pub start: Vec<u8>,
+    pub end: Vec<u8>,
+}
+
+impl RangeTombstone {
+    pub fn covers(&self, comparator: &dyn Comparator, key: &[u8]) -> bool {
+        comparator.compare(&self.start, key) != Ordering::Greater
+            && comparator.compare(key, &self.end) == Ordering::Less
+    }
+
+    // the part of it in [lower, upper), None if there is none,
+    // a missing bound doesn't cut anything off
+    pub fn clip(
+        &self,
+        comparator: &dyn Comparator,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+    ) -> Option<Self> {
+        let mut clipped = self.clone();
+        if let Some(lower) = lower {
+            if comparator.compare(&clipped.start, lower) == Ordering::Less {
+                clipped.start = lower.to_vec();
+            }
+        }
+        if let Some(upper) = upper {
+            if comparator.compare(upper, &clipped.end) == Ordering::Less {
+                clipped.end = upper.to_vec();
+            }
+        }
+        (comparator.compare(&clipped.start, &clipped.end) == Ordering::Less)
+            .then_some(clipped)
+    }
+}
+
+// the sequence number of the newest of tombstones covering key
+// that is no newer than sequence
+pub fn newest_covering<'a>(
+    comparator: &dyn Comparator,
+    tombstones: impl IntoIterator<Item = &'a RangeTombstone>,
+    key: &[u8],
+    sequence: u64,
+) -> Option<u64> {
+    tombstones
+        .into_iter()
+        .filter(|tombstone| {
+            tombstone.sequence <= sequence && tombstone.covers(comparator, key)
+        })
+        .map(|tombstone| tombstone.sequence)
+        .max()
+}
+
+// range tombstones cut at every start and end into pieces that don't
+// overlap, in order, each with the sequence numbers of the tombstones
+// over it, newest first
+//
+// made once for a whole iterator or table, the tombstones covering a
+// key are then a binary search away instead of a scan of all of them
+#[derive(Debug, Default)]
+pub struct FragmentedTombstones {
+    fragments: Vec<Fragment>,
+}
+
+#[derive(Debug)]
+struct Fragment {
+    start: Vec<u8>,
+    end: Vec<u8>,
+    sequences: Vec<u64>,
+}
+
+impl FragmentedTombstones {
+    pub fn new(
+        comparator: &dyn Comparator,
+        tombstones: &[RangeTombstone],
+    ) -> Self {
+        let mut by_start = tombstones.iter().collect::<Vec<_>>();
+        by_start.sort_by(|a, b| comparator.compare(&a.start, &b.start));
+        let mut bounds = tombstones
+            .iter()
+            .flat_map(|tombstone| [&tombstone.start, &tombstone.end])
+            .collect::<Vec<_>>();
+        bounds.sort_by(|a, b| comparator.compare(a, b));
+        bounds.dedup_by(|a, b| comparator.compare(a, b) == Ordering::Equal);
+
+        // the tombstones over each piece between two bounds in a row
+        let mut fragments = vec![];
+        let mut active: Vec<&RangeTombstone> = vec![];
+        let mut next = by_start.into_iter().peekable();
+        for pair in bounds.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            while let Some(tombstone) = next.next_if(|tombstone| {
+                comparator.compare(&tombstone.start, start) != Ordering::Greater
+            }) {
+                active.push(tombstone);
+            }
+            active.retain(|tombstone| {
+                comparator.compare(&tombstone.end, start) == Ordering::Greater
+            });
+            if active.is_empty() {
+                continue;
+            }
+            let mut sequences = active
+                .iter()
+                .map(|tombstone| tombstone.sequence)
+                .collect::<Vec<_>>();
+            sequences.sort_unstable_by(|a, b| b.cmp(a));
+            fragments.push(Fragment {
+                start: start.clone(),
+                end: end.clone(),
+                sequences,
+            });
+        }
+        Self { fragments }
+    }
+
+    // same as newest_covering, over all of the tombstones
+    pub fn newest_covering(
+        &self,
+        comparator: &dyn Comparator,
+        key: &[u8],
+        sequence: u64,
+    ) -> Option<u64> {
+        let i = self.fragments.partition_point(|fragment| {
+            comparator.compare(&fragment.start, key) != Ordering::Greater
+        });
+        let fragment = self.fragments.get(i.checked_sub(1)?)?;
+        if comparator.compare(key, &fragment.end) != Ordering::Less {
+            return None;
+        }
+        let j = fragment.sequences.partition_point(|s| *s > sequence);
+        fragment.sequences.get(j).copied()
+    }
+}
+
+#[test]
+fn test_range_tombstone() {
+    use crate::comparator::BytewiseComparator;
+
+    let tombstone = |sequence, start: &[u8], end: &[u8]| RangeTombstone {
+        sequence,
+        start: start.to_vec(),
+        end: end.to_vec(),
+    };
+    let cmp = &BytewiseComparator;
+    let tombstones = [tombstone(5, b"b", b"d"), tombstone(9, b"c", b"f")];
+    assert_eq!(newest_covering(cmp, &tombstones, b"a", 10), None);
+    assert_eq!(newest_covering(cmp, &tombstones, b"b", 10), Some(5));
+    assert_eq!(newest_covering(cmp, &tombstones, b"c", 10), Some(9));
+    // too new to be seen
+    assert_eq!(newest_covering(cmp, &tombstones, b"c", 8), Some(5));
+    // the end is left out
+    assert_eq!(newest_covering(cmp, &tombstones, b"f", 10), None);
+
+    // the fragments agree with a scan of them all
+    let tombstones = [
+        tombstone(5, b"b", b"d"),
+        tombstone(9, b"c", b"f"),
+        tombstone(3, b"a", b"z"),
+        tombstone(7, b"h", b"k"),
+        tombstone(8, b"c", b"d"),
+    ];
+    let fragmented = FragmentedTombstones::new(cmp, &tombstones);
+    for key in [b"0", b"a", b"b", b"c", b"d", b"e", b"g", b"h", b"y", b"z"] {
+        for sequence in 0..11 {
+            assert_eq!(
+                fragmented.newest_covering(cmp, key, sequence),
+                newest_covering(cmp, &tombstones, key, sequence),
+            );
+        }
+    }
+    let none = FragmentedTombstones::new(cmp, &[]);
+    assert_eq!(none.newest_covering(cmp, b"a", 10), None);
+
+    let tombstones = [tombstone(9, b"c", b"f")];
+    let clip = |lower: Option<&[u8]>, upper: Option<&[u8]>| {
+        tombstones[0].clip(cmp, lower, upper)
+    };
+    assert_eq!(clip(None, None), Some(tombstones[0].clone()));
+    assert_eq!(clip(Some(b"d"), Some(b"e")), Some(tombstone(9, b"d", b"e")));
+    assert_eq!(clip(Some(b"a"), Some(b"z")), Some(tombstones[0].clone()));
+    assert_eq!(clip(Some(b"f"), None), None);
+    assert_eq!(clip(None, Some(b"c")), None);
+}
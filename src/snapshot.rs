@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// the sequence numbers of the snapshots in use, with how many
+// of them share each one
+#[derive(Default)]
+pub struct SnapshotList {
+    sequences: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl SnapshotList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn acquire(self: &Arc<Self>, sequence: u64) -> Snapshot {
+        *self.sequences.lock().unwrap().entry(sequence).or_default() += 1;
+        Snapshot {
+            sequence,
+            list: self.clone(),
+        }
+    }
+
+    // compactions keep every version this one may still see
+    pub fn oldest(&self) -> Option<u64> {
+        self.sequences.lock().unwrap().keys().next().copied()
+    }
+
+    fn release(&self, sequence: u64) {
+        let mut sequences = self.sequences.lock().unwrap();
+        let count = sequences.get_mut(&sequence).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            sequences.remove(&sequence);
+        }
+    }
+}
+
+// the database as of some write, reads through it ignore every
+// write after that one for as long as it is held
+pub struct Snapshot {
+    sequence: u64,
+    list: Arc<SnapshotList>,
+}
+
+impl Snapshot {
+    pub(crate) fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.list.release(self.sequence);
+    }
+}
+
+#[test]
+fn test_snapshot_list() {
+    let list = Arc::new(SnapshotList::new());
+    assert_eq!(list.oldest(), None);
+    let newer = list.acquire(5);
+    let older = list.acquire(3);
+    let shared = list.acquire(3);
+    assert_eq!(newer.sequence(), 5);
+    assert_eq!(list.oldest(), Some(3));
+    drop(older);
+    assert_eq!(list.oldest(), Some(3));
+    drop(shared);
+    assert_eq!(list.oldest(), Some(5));
+    drop(newer);
+    assert_eq!(list.oldest(), None);
+}
@@ -0,0 +1,755 @@
+use crate::block::{Block, BlockBuilder, BlockIter};
+use crate::cache::BlockCache;
+use crate::coding;
+use crate::comparator::{BytewiseComparator, Comparator};
+use crate::compress::{Compressor, LzCompressor};
+use crate::crc32c;
+use crate::filter::FilterPolicy;
+use crate::iterator::{Entry, InternalIterator};
+use crate::{Error, Options, ReadOptions};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
+
+// table layout:
+// data block* | metaindex block | index block | footer
+//
+// every block is followed by a trailer:
+// compression type (u8) | crc32c of the block and the type (u32)
+//
+// the type is the id of the compressor of a data block, 0 if it is
+// stored as is, which the other blocks always are
+//
+// the index block maps a key between the last key of each data block
+// and the first key of the next (or past the last key of the table)
+// to the handle of the block, the metaindex block maps
+// "filter.<policy name>" to the filter block when the table was built
+// with a filter policy, the footer holds the handles of the metaindex
+// and index blocks, zero padded to a fixed size, followed by the magic
+const TABLE_MAGIC: u64 = 0x5242_5255_5353_5431;
+const BLOCK_TRAILER_LEN: usize = 5;
+const NO_COMPRESSION: u8 = 0;
+// two handles of at most two 10 byte varints each, plus the magic
+const FOOTER_LEN: usize = 2 * 2 * 10 + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BlockHandle {
+    offset: u64,
+    // without the trailer
+    size: u64,
+}
+
+impl BlockHandle {
+    fn encode_to(&self, dst: &mut Vec<u8>) {
+        coding::put_varint(dst, self.offset);
+        coding::put_varint(dst, self.size);
+    }
+
+    fn decode(src: &[u8]) -> Option<(Self, usize)> {
+        let (offset, n0) = coding::get_varint(src)?;
+        let (size, n1) = coding::get_varint(&src[n0..])?;
+        Some((Self { offset, size }, n0 + n1))
+    }
+}
+
+// writes a table out of key/value pairs added in increasing key order
+pub struct TableBuilder {
+    writer: io::BufWriter<File>,
+    comparator: Arc<dyn Comparator>,
+    offset: u64,
+    data_block: BlockBuilder,
+    block_size: usize,
+    compressor: Option<Arc<dyn Compressor>>,
+    min_compression_savings: u8,
+    index_block: BlockBuilder,
+    last_key: Vec<u8>,
+    num_entries: u64,
+    // a block was just written, its index entry waits for the first
+    // key of the next one to pick a short separator
+    pending_handle: Option<BlockHandle>,
+    filter_policy: Option<Arc<dyn FilterPolicy>>,
+    // every key added, back to back, for the filter
+    filter_keys: Vec<u8>,
+    filter_key_lens: Vec<usize>,
+}
+
+impl TableBuilder {
+    pub fn new(
+        file: File,
+        options: &Options,
+        comparator: Arc<dyn Comparator>,
+    ) -> Self {
+        Self {
+            writer: io::BufWriter::new(file),
+            comparator,
+            offset: 0,
+            data_block: BlockBuilder::new(options.block_restart_interval),
+            block_size: options.block_size,
+            compressor: options.compressor.clone(),
+            min_compression_savings: options.min_compression_savings,
+            // index entries are looked up one by one, no point sharing
+            index_block: BlockBuilder::new(1),
+            last_key: vec![],
+            num_entries: 0,
+            pending_handle: None,
+            filter_policy: options.filter_policy.clone(),
+            filter_keys: vec![],
+            filter_key_lens: vec![],
+        }
+    }
+
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        debug_assert!(
+            self.num_entries == 0
+                || self.comparator.compare(key, &self.last_key)
+                    == Ordering::Greater
+        );
+        if let Some(handle) = self.pending_handle.take() {
+            self.comparator
+                .find_shortest_separator(&mut self.last_key, key);
+            self.add_index_entry(handle);
+        }
+        if self.filter_policy.is_some() {
+            self.filter_keys.extend_from_slice(key);
+            self.filter_key_lens.push(key.len());
+        }
+        self.data_block.add(key, value);
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.num_entries += 1;
+        if self.data_block.size_estimate() >= self.block_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // size of the file if it was finished right now, roughly
+    pub fn file_size(&self) -> u64 {
+        self.offset + self.data_block.size_estimate() as u64
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.data_block.is_empty() {
+            return Ok(());
+        }
+        let contents = self.data_block.finish();
+        let handle = match &self.compressor {
+            Some(compressor) => {
+                let compressed = compressor.compress(&contents);
+                // not worth decompressing on every read otherwise
+                let savings = contents.len()
+                    * self.min_compression_savings as usize
+                    / 100;
+                if compressed.len() <= contents.len() - savings {
+                    let id = compressor.id();
+                    self.write_block(&compressed, id)?
+                } else {
+                    self.write_block(&contents, NO_COMPRESSION)?
+                }
+            }
+            None => self.write_block(&contents, NO_COMPRESSION)?,
+        };
+        self.pending_handle = Some(handle);
+        Ok(())
+    }
+
+    fn add_index_entry(&mut self, handle: BlockHandle) {
+        let mut encoded = vec![];
+        handle.encode_to(&mut encoded);
+        self.index_block.add(&self.last_key, &encoded);
+    }
+
+    fn write_block(
+        &mut self,
+        contents: &[u8],
+        compression: u8,
+    ) -> Result<BlockHandle, Error> {
+        let handle = BlockHandle {
+            offset: self.offset,
+            size: contents.len() as u64,
+        };
+        let crc = crc32c::extend(crc32c::value(contents), &[compression]);
+        self.writer.write_all(contents)?;
+        self.writer.write_all(&[compression])?;
+        self.writer.write_all(&crc.to_le_bytes())?;
+        self.offset += (contents.len() + BLOCK_TRAILER_LEN) as u64;
+        Ok(handle)
+    }
+
+    // writes out the remaining blocks and the footer and syncs the file,
+    // returns its size
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.flush()?;
+        if let Some(handle) = self.pending_handle.take() {
+            self.comparator.find_short_successor(&mut self.last_key);
+            self.add_index_entry(handle);
+        }
+        let mut metaindex = BlockBuilder::new(1);
+        if let Some(policy) = self.filter_policy.clone() {
+            let mut keys = Vec::with_capacity(self.filter_key_lens.len());
+            let mut start = 0;
+            for len in &self.filter_key_lens {
+                keys.push(&self.filter_keys[start..start + len]);
+                start += len;
+            }
+            let filter = policy.create_filter(&keys);
+            let handle = self.write_block(&filter, NO_COMPRESSION)?;
+            let mut encoded = vec![];
+            handle.encode_to(&mut encoded);
+            metaindex
+                .add(filter_block_name(policy.as_ref()).as_bytes(), &encoded);
+        }
+        let metaindex = metaindex.finish();
+        let metaindex_handle = self.write_block(&metaindex, NO_COMPRESSION)?;
+        let index = self.index_block.finish();
+        let index_handle = self.write_block(&index, NO_COMPRESSION)?;
+
+        let mut footer = vec![];
+        metaindex_handle.encode_to(&mut footer);
+        index_handle.encode_to(&mut footer);
+        footer.resize(FOOTER_LEN - 8, 0);
+        footer.extend_from_slice(&TABLE_MAGIC.to_le_bytes());
+        self.writer.write_all(&footer)?;
+        self.offset += FOOTER_LEN as u64;
+
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        Ok(self.offset)
+    }
+}
+
+fn corruption(offset: u64, reason: &str) -> Error {
+    Error::Corruption {
+        offset: Some(offset),
+        reason: reason.to_string(),
+    }
+}
+
+fn filter_block_name(policy: &dyn FilterPolicy) -> String {
+    format!("filter.{}", policy.name())
+}
+
+// an immutable sorted table written by TableBuilder
+pub struct Table {
+    file: TableFile,
+    // data blocks are looked up in it by the table's file number
+    // before being read from the file
+    block_cache: Option<(Arc<BlockCache>, u64)>,
+    // reads the data blocks it compressed, besides the built-in one
+    compressor: Option<Arc<dyn Compressor>>,
+    // the order the table was built in
+    comparator: Arc<dyn Comparator>,
+    index: Arc<Block>,
+    // where the index block starts, for errors in it
+    index_offset: u64,
+    // the filter block and the policy that understands it
+    filter: Option<(Arc<dyn FilterPolicy>, Vec<u8>)>,
+}
+
+impl Table {
+    pub fn open(
+        file: File,
+        size: u64,
+        options: &Options,
+        comparator: Arc<dyn Comparator>,
+    ) -> Result<Self, Error> {
+        if size < FOOTER_LEN as u64 {
+            return Err(Error::Corruption {
+                offset: None,
+                reason: "file too short to be a table".to_string(),
+            });
+        }
+        let file = TableFile { file, size };
+        let footer_offset = size - FOOTER_LEN as u64;
+        let footer = file.read(footer_offset, FOOTER_LEN)?;
+        let magic =
+            u64::from_le_bytes(footer[FOOTER_LEN - 8..].try_into().unwrap());
+        if magic != TABLE_MAGIC {
+            return Err(corruption(footer_offset, "bad table magic"));
+        }
+        let (metaindex_handle, n) = BlockHandle::decode(&footer)
+            .ok_or_else(|| corruption(footer_offset, "bad metaindex handle"))?;
+        let (index_handle, _) = BlockHandle::decode(&footer[n..])
+            .ok_or_else(|| corruption(footer_offset, "bad index handle"))?;
+
+        let index = read_block(&file, index_handle)?;
+        let mut filter = None;
+        if let Some(policy) = &options.filter_policy {
+            // a table built with a different policy (or none)
+            // just goes without
+            let name = filter_block_name(policy.as_ref());
+            let metaindex = read_block(&file, metaindex_handle)?;
+            let mut metaindex = BlockIter::new(
+                Arc::new(metaindex),
+                Arc::new(BytewiseComparator),
+            );
+            metaindex.seek(name.as_bytes());
+            if metaindex.valid() && metaindex.key() == name.as_bytes() {
+                let handle =
+                    decode_handle(metaindex.value(), metaindex_handle.offset)?;
+                let block = read_block_contents(&file, handle, true, None)?;
+                filter = Some((policy.clone(), block));
+            }
+        }
+        Ok(Self {
+            file,
+            block_cache: None,
+            compressor: options.compressor.clone(),
+            comparator,
+            index: Arc::new(index),
+            index_offset: index_handle.offset,
+            filter,
+        })
+    }
+
+    pub fn with_block_cache(
+        mut self,
+        block_cache: Arc<BlockCache>,
+        file_number: u64,
+    ) -> Self {
+        self.block_cache = Some((block_cache, file_number));
+        self
+    }
+
+    fn read_block(
+        &self,
+        handle: BlockHandle,
+        options: &ReadOptions,
+    ) -> Result<Arc<Block>, Error> {
+        if let Some((cache, number)) = &self.block_cache {
+            if let Some(block) = cache.get(*number, handle.offset) {
+                return Ok(block);
+            }
+        }
+        let contents = read_block_contents(
+            &self.file,
+            handle,
+            options.verify_checksums,
+            self.compressor.as_deref(),
+        )?;
+        let block = Block::new(contents)
+            .ok_or_else(|| corruption(handle.offset, "bad block"))?;
+        let block = Arc::new(block);
+        // a block that wasn't checked could be handed from the cache
+        // to a read that wants it checked
+        if let Some((cache, number)) = &self.block_cache {
+            if options.fill_cache && options.verify_checksums {
+                cache.insert(*number, handle.offset, block.clone());
+            }
+        }
+        Ok(block)
+    }
+
+    // false only if the filter rules key out
+    pub fn key_may_match(&self, key: &[u8]) -> bool {
+        match &self.filter {
+            Some((policy, filter)) => policy.key_may_match(key, filter),
+            None => true,
+        }
+    }
+
+    // the first entry at or after key, None if there is none
+    // or the filter rules key out
+    pub fn get(
+        self: &Arc<Self>,
+        key: &[u8],
+        options: &ReadOptions,
+    ) -> Result<Option<Entry>, Error> {
+        if !self.key_may_match(key) {
+            return Ok(None);
+        }
+        let mut iter = self.iter(options);
+        iter.seek(key);
+        iter.status()?;
+        if iter.valid() {
+            return Ok(Some((iter.key().to_vec(), iter.value().to_vec())));
+        }
+        Ok(None)
+    }
+
+    pub fn iter(self: &Arc<Self>, options: &ReadOptions) -> TableIterator {
+        TableIterator {
+            table: self.clone(),
+            options: options.without_snapshot(),
+            index: BlockIter::new(self.index.clone(), self.comparator.clone()),
+            data: None,
+            data_offset: 0,
+            err: None,
+        }
+    }
+}
+
+// the file of a table, read at offsets instead of through a shared
+// cursor so any number of readers can be at it at once
+struct TableFile {
+    file: File,
+    size: u64,
+}
+
+impl TableFile {
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0; len];
+        self.file.read_exact_at(&mut buf, offset)?;
+        Ok(buf)
+    }
+}
+
+// src is an entry of the block at block_offset
+fn decode_handle(src: &[u8], block_offset: u64) -> Result<BlockHandle, Error> {
+    BlockHandle::decode(src)
+        .map(|(handle, _)| handle)
+        .ok_or_else(|| corruption(block_offset, "bad block handle"))
+}
+
+// the blocks read at open are always checked
+fn read_block(file: &TableFile, handle: BlockHandle) -> Result<Block, Error> {
+    let contents = read_block_contents(file, handle, true, None)?;
+    Block::new(contents).ok_or_else(|| corruption(handle.offset, "bad block"))
+}
+
+// reads the block at handle and checks its trailer, the checksum
+// only if verify_checksum is set, a compressed block comes back
+// decompressed by the built-in compressor or by compressor
+fn read_block_contents(
+    file: &TableFile,
+    handle: BlockHandle,
+    verify_checksum: bool,
+    compressor: Option<&dyn Compressor>,
+) -> Result<Vec<u8>, Error> {
+    let len = handle
+        .size
+        .checked_add(BLOCK_TRAILER_LEN as u64)
+        .filter(|len| handle.offset.saturating_add(*len) <= file.size)
+        .ok_or_else(|| corruption(handle.offset, "truncated block"))?;
+    let mut buf = file.read(handle.offset, len as usize)?;
+    let trailer = buf.split_off(handle.size as usize);
+    if verify_checksum {
+        let crc = crc32c::extend(crc32c::value(&buf), &trailer[..1]);
+        if crc.to_le_bytes() != trailer[1..] {
+            return Err(corruption(handle.offset, "checksum mismatch"));
+        }
+    }
+    let compressor = match trailer[0] {
+        NO_COMPRESSION => return Ok(buf),
+        LzCompressor::ID => &LzCompressor,
+        id => match compressor {
+            Some(compressor) if compressor.id() == id => compressor,
+            _ => {
+                return Err(corruption(
+                    handle.offset,
+                    "unknown compression type",
+                ))
+            }
+        },
+    };
+    compressor
+        .decompress(&buf)
+        .ok_or_else(|| corruption(handle.offset, "bad compressed block"))
+}
+
+// cursor over the entries of a table, walks the index block
+// and reads data blocks as it goes
+pub struct TableIterator {
+    table: Arc<Table>,
+    options: ReadOptions<'static>,
+    index: BlockIter,
+    data: Option<BlockIter>,
+    data_offset: u64,
+    err: Option<Error>,
+}
+
+impl TableIterator {
+    fn init_data_block(&mut self) {
+        self.data = None;
+        if !self.index.valid() {
+            if self.index.corrupted() {
+                let offset = self.table.index_offset;
+                self.err = Some(corruption(offset, "bad index block"));
+            }
+            return;
+        }
+        let index_value = self.index.value();
+        let index_offset = self.table.index_offset;
+        let block =
+            decode_handle(index_value, index_offset).and_then(|handle| {
+                self.data_offset = handle.offset;
+                self.table.read_block(handle, &self.options)
+            });
+        match block {
+            Ok(block) => {
+                let comparator = self.table.comparator.clone();
+                self.data = Some(BlockIter::new(block, comparator));
+            }
+            Err(e) => self.err = Some(e),
+        }
+    }
+
+    // moves on to the next block until there is an entry to be at,
+    // or back to the previous one when going backwards
+    fn skip_empty_blocks(&mut self, backwards: bool) {
+        while let Some(data) = &self.data {
+            if data.valid() {
+                return;
+            }
+            if data.corrupted() {
+                self.err =
+                    Some(corruption(self.data_offset, "bad block entry"));
+                self.data = None;
+                return;
+            }
+            if backwards {
+                self.index.prev();
+            } else {
+                self.index.next();
+            }
+            self.init_data_block();
+            if let Some(data) = &mut self.data {
+                if backwards {
+                    data.seek_to_last();
+                } else {
+                    data.seek_to_first();
+                }
+            }
+        }
+    }
+}
+
+impl InternalIterator for TableIterator {
+    fn valid(&self) -> bool {
+        self.data.as_ref().is_some_and(BlockIter::valid)
+    }
+
+    fn key(&self) -> &[u8] {
+        self.data.as_ref().unwrap().key()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.data.as_ref().unwrap().value()
+    }
+
+    fn seek_to_first(&mut self) {
+        self.index.seek_to_first();
+        self.init_data_block();
+        if let Some(data) = &mut self.data {
+            data.seek_to_first();
+        }
+        self.skip_empty_blocks(false);
+    }
+
+    fn seek_to_last(&mut self) {
+        self.index.seek_to_last();
+        self.init_data_block();
+        if let Some(data) = &mut self.data {
+            data.seek_to_last();
+        }
+        self.skip_empty_blocks(true);
+    }
+
+    fn seek(&mut self, target: &[u8]) {
+        self.index.seek(target);
+        self.init_data_block();
+        if let Some(data) = &mut self.data {
+            data.seek(target);
+        }
+        self.skip_empty_blocks(false);
+    }
+
+    fn next(&mut self) {
+        self.data.as_mut().unwrap().next();
+        self.skip_empty_blocks(false);
+    }
+
+    fn prev(&mut self) {
+        self.data.as_mut().unwrap().prev();
+        self.skip_empty_blocks(true);
+    }
+
+    fn status(&mut self) -> Result<(), Error> {
+        match self.err.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+fn build_test_table(path: &str, n: usize) -> Arc<Table> {
+    let options = Options::default();
+    let file = File::create(path).unwrap();
+    let mut builder =
+        TableBuilder::new(file, &options, Arc::new(BytewiseComparator));
+    for i in 0..n {
+        let key = format!("key{:05}", i * 2);
+        builder
+            .add(key.as_bytes(), format!("value{i}").as_bytes())
+            .unwrap();
+    }
+    let size = builder.finish().unwrap();
+    let file = File::open(path).unwrap();
+    Arc::new(
+        Table::open(file, size, &options, Arc::new(BytewiseComparator))
+            .unwrap(),
+    )
+}
+
+// the value stored under exactly key, if any
+#[cfg(test)]
+fn lookup(table: &Arc<Table>, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let entry = table.get(key, &ReadOptions::default())?;
+    Ok(entry.filter(|(k, _)| k == key).map(|(_, value)| value))
+}
+
+#[test]
+fn test_table() {
+    use crate::iterator::entries;
+
+    let table = build_test_table("/tmp/test_table", 1000);
+
+    assert_eq!(
+        lookup(&table, b"key00000").unwrap(),
+        Some(b"value0".to_vec())
+    );
+    assert_eq!(
+        lookup(&table, b"key01998").unwrap(),
+        Some(b"value999".to_vec())
+    );
+    assert_eq!(lookup(&table, b"key00001").unwrap(), None);
+    assert_eq!(lookup(&table, b"a").unwrap(), None);
+    assert_eq!(table.get(b"z", &ReadOptions::default()).unwrap(), None);
+
+    let entries = entries(table.iter(&ReadOptions::default()))
+        .collect::<Result<Vec<_>, _>>();
+    let entries = entries.unwrap();
+    assert_eq!(entries.len(), 1000);
+    assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(entries[500], (b"key01000".to_vec(), b"value500".to_vec()));
+
+    let mut iter = table.iter(&ReadOptions::default());
+    iter.seek(b"key01001");
+    assert_eq!(iter.key(), b"key01002");
+    iter.next();
+    assert_eq!(iter.key(), b"key01004");
+    iter.seek(b"key9");
+    assert!(!iter.valid());
+
+    // backwards, across every block
+    iter.seek_to_last();
+    for entry in entries.iter().rev() {
+        assert_eq!((iter.key(), iter.value()), (&entry.0[..], &entry.1[..]));
+        iter.prev();
+    }
+    assert!(!iter.valid());
+    iter.status().unwrap();
+}
+
+#[test]
+fn test_table_without_filter() {
+    let table = build_test_table("/tmp/test_table_without_filter", 100);
+    assert!(table.filter.is_some());
+
+    // opening with another policy, or none, ignores the filter
+    let options = Options {
+        filter_policy: None,
+        ..Options::default()
+    };
+    let file = File::open("/tmp/test_table_without_filter").unwrap();
+    let size = file.metadata().unwrap().len();
+    let table = Table::open(file, size, &options, Arc::new(BytewiseComparator));
+    let table = Arc::new(table.unwrap());
+    assert!(table.filter.is_none());
+    assert_eq!(
+        lookup(&table, b"key00010").unwrap(),
+        Some(b"value5".to_vec())
+    );
+    // without a filter the next entry comes back
+    assert_eq!(
+        table.get(b"key00011", &ReadOptions::default()).unwrap(),
+        Some((b"key00012".to_vec(), b"value6".to_vec()))
+    );
+}
+
+#[test]
+fn test_empty_table() {
+    use crate::iterator::entries;
+
+    let table = build_test_table("/tmp/test_empty_table", 0);
+    assert_eq!(table.get(b"a", &ReadOptions::default()).unwrap(), None);
+    assert_eq!(entries(table.iter(&ReadOptions::default())).count(), 0);
+    let mut iter = table.iter(&ReadOptions::default());
+    iter.seek_to_last();
+    assert!(!iter.valid());
+}
+
+#[test]
+fn test_table_corruption() {
+    use crate::iterator::entries;
+
+    let path = "/tmp/test_table_corruption";
+    let table = build_test_table(path, 1000);
+
+    // flip a byte in the middle of the first data block
+    let mut bytes = std::fs::read(path).unwrap();
+    bytes[100] ^= 0xff;
+    std::fs::write(path, &bytes).unwrap();
+
+    assert!(matches!(
+        table.get(b"key00000", &ReadOptions::default()),
+        Err(Error::Corruption { offset: Some(0), reason })
+            if reason == "checksum mismatch"
+    ));
+    // the flipped byte is in a later entry of the block
+    let unchecked = ReadOptions::default().verify_checksums(false);
+    let (key, _) = table.get(b"key00000", &unchecked).unwrap().unwrap();
+    assert_eq!(key, b"key00000");
+    // the filter rules this one out before the block is read
+    assert_eq!(lookup(&table, b"key00001").unwrap(), None);
+    let mut entries = entries(table.iter(&ReadOptions::default()));
+    assert!(entries.next().unwrap().is_err());
+
+    // the footer is checked on open
+    let len = bytes.len();
+    bytes[len - 1] ^= 0xff;
+    std::fs::write(path, &bytes).unwrap();
+    let file = File::open(path).unwrap();
+    let comparator = Arc::new(BytewiseComparator);
+    let options = Options::default();
+    assert!(Table::open(file, len as u64, &options, comparator).is_err());
+}
+
+#[test]
+fn test_table_compression() {
+    use crate::iterator::entries;
+
+    let path = "/tmp/test_table_compression";
+    let build = |options: &Options| {
+        let file = File::create(path).unwrap();
+        let mut builder =
+            TableBuilder::new(file, options, Arc::new(BytewiseComparator));
+        for i in 0..1000 {
+            let key = format!("key{i:05}");
+            let value = format!(r#"{{"id":{i},"name":"user","active":true}}"#);
+            builder.add(key.as_bytes(), value.as_bytes()).unwrap();
+        }
+        builder.finish().unwrap()
+    };
+    let raw = build(&Options::default().compressor(None));
+    let compressed = build(&Options::default());
+    assert!(compressed < raw / 2);
+
+    // not shrinking enough, so stored as is
+    let options = Options::default().min_compression_savings(100);
+    assert_eq!(build(&options), raw);
+
+    let size = build(&Options::default());
+    let file = File::open(path).unwrap();
+    let options = Options::default();
+    let table = Table::open(file, size, &options, Arc::new(BytewiseComparator));
+    let table = Arc::new(table.unwrap());
+    assert_eq!(entries(table.iter(&ReadOptions::default())).count(), 1000);
+    assert_eq!(
+        lookup(&table, b"key00500").unwrap(),
+        Some(br#"{"id":500,"name":"user","active":true}"#.to_vec())
+    );
+}
@@ -0,0 +1,107 @@
+use crate::cache::{BlockCache, CacheStats};
+use crate::comparator::Comparator;
+use crate::filename::{file_name, FileType};
+use crate::options::NUM_NON_TABLE_FILES;
+use crate::table::Table;
+use crate::version_edit::FileMetaData;
+use crate::{Error, Options};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+// keeps the tables of a database open, by file number, up to
+// max_open_files minus the ones kept for other files, the table
+// used the longest ago is closed to make room
+pub struct TableCache {
+    dir: PathBuf,
+    options: Options,
+    comparator: Arc<dyn Comparator>,
+    capacity: usize,
+    tables: Mutex<Tables>,
+    // shared by every table it opens
+    block_cache: Option<Arc<BlockCache>>,
+}
+
+struct Tables {
+    // along with when each was last used
+    open: HashMap<u64, (Arc<Table>, u64)>,
+    clock: u64,
+}
+
+impl TableCache {
+    pub fn new(
+        dir: PathBuf,
+        options: Options,
+        comparator: Arc<dyn Comparator>,
+    ) -> Self {
+        let capacity =
+            options.max_open_files.saturating_sub(NUM_NON_TABLE_FILES);
+        let block_cache = (options.block_cache_capacity > 0)
+            .then(|| Arc::new(BlockCache::new(options.block_cache_capacity)));
+        Self {
+            dir,
+            options,
+            comparator,
+            capacity: capacity.max(1),
+            tables: Mutex::new(Tables {
+                open: HashMap::new(),
+                clock: 0,
+            }),
+            block_cache,
+        }
+    }
+
+    pub fn get(&self, file: &FileMetaData) -> Result<Arc<Table>, Error> {
+        {
+            let mut tables = self.tables.lock().unwrap();
+            tables.clock += 1;
+            let clock = tables.clock;
+            if let Some((table, used)) = tables.open.get_mut(&file.number) {
+                *used = clock;
+                return Ok(table.clone());
+            }
+        }
+        // opened without the lock, whoever gets there last wins
+        let path = file_name(&self.dir, file.number, FileType::Table);
+        let table = Table::open(
+            File::open(path)?,
+            file.size,
+            &self.options,
+            self.comparator.clone(),
+        )?;
+        let table = match &self.block_cache {
+            Some(cache) => table.with_block_cache(cache.clone(), file.number),
+            None => table,
+        };
+        let table = Arc::new(table);
+        let mut tables = self.tables.lock().unwrap();
+        if tables.open.len() >= self.capacity
+            && !tables.open.contains_key(&file.number)
+        {
+            // whoever still has it open keeps it open
+            let oldest = tables
+                .open
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(number, _)| *number);
+            if let Some(oldest) = oldest {
+                tables.open.remove(&oldest);
+            }
+        }
+        let clock = tables.clock;
+        tables.open.insert(file.number, (table.clone(), clock));
+        Ok(table)
+    }
+
+    pub fn block_cache_stats(&self) -> CacheStats {
+        self.block_cache
+            .as_ref()
+            .map_or(CacheStats::default(), |cache| cache.stats())
+    }
+
+    // called once the table is gone for good
+    pub fn evict(&self, number: u64) {
+        self.tables.lock().unwrap().open.remove(&number);
+    }
+}
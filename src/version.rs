@@ -0,0 +1,779 @@
+use crate::comparator::Comparator;
+use crate::dbformat::{
+    internal_key, parse_internal_key, user_key, InternalKeyComparator,
+    RecordKind, KIND_FOR_SEEK,
+};
+use crate::filename::{self, file_name, FileType};
+use crate::iterator::{InternalIterator, Source};
+use crate::log;
+use crate::range_del::{FragmentedTombstones, RangeTombstone};
+use crate::table::TableIterator;
+use crate::table_cache::TableCache;
+use crate::version_edit::{FileMetaData, VersionEdit};
+use crate::{Error, Options, ReadOptions};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+
+pub const NUM_LEVELS: usize = 7;
+
+// the set of tables making up the database at some point
+//
+// level 0 tables are memtables written out as they are, so they
+// may overlap each other and are kept oldest first, the tables of
+// every other level cover disjoint key ranges and are kept sorted,
+// no user key is ever split between two tables of the same level,
+// though a table's range may end right before the first version of
+// the key the next one starts at when a range deletion is cut there
+#[derive(Clone)]
+pub struct Version {
+    comparator: Arc<InternalKeyComparator>,
+    pub files: [Vec<Arc<FileMetaData>>; NUM_LEVELS],
+    // the range tombstones of the tables with any, by file number,
+    // cut up once for every read to search
+    fragmented_tombstones: HashMap<u64, Arc<FragmentedTombstones>>,
+}
+
+impl Version {
+    pub fn new(comparator: Arc<InternalKeyComparator>) -> Self {
+        Self {
+            comparator,
+            files: Default::default(),
+            fragmented_tombstones: HashMap::new(),
+        }
+    }
+
+    fn user_comparator(&self) -> &dyn Comparator {
+        self.comparator.user_comparator().as_ref()
+    }
+
+    // the table of a sorted level that could hold the internal key
+    fn find_file<'a>(
+        &self,
+        files: &'a [Arc<FileMetaData>],
+        key: &[u8],
+    ) -> Option<&'a Arc<FileMetaData>> {
+        let i = files.partition_point(|file| {
+            self.comparator.compare(&file.largest, key) == Ordering::Less
+        });
+        files
+            .get(i)
+            .filter(|file| file.contains(self.user_comparator(), user_key(key)))
+    }
+
+    // smallest and largest internal key of a non empty set of tables
+    fn key_range(&self, files: &[Arc<FileMetaData>]) -> (Vec<u8>, Vec<u8>) {
+        let cmp = |a: &&Vec<u8>, b: &&Vec<u8>| self.comparator.compare(a, b);
+        let smallest = files.iter().map(|file| &file.smallest).min_by(cmp);
+        let largest = files.iter().map(|file| &file.largest).max_by(cmp);
+        (smallest.unwrap().clone(), largest.unwrap().clone())
+    }
+
+    pub fn apply(&self, edit: &VersionEdit) -> Self {
+        let mut version = self.clone();
+        for (level, number) in &edit.deleted {
+            version.files[*level].retain(|file| file.number != *number);
+            version.fragmented_tombstones.remove(number);
+        }
+        for (level, file) in &edit.added {
+            version.files[*level].push(file.clone());
+            if !file.range_tombstones.is_empty() {
+                let fragmented = FragmentedTombstones::new(
+                    self.user_comparator(),
+                    &file.range_tombstones,
+                );
+                version
+                    .fragmented_tombstones
+                    .insert(file.number, Arc::new(fragmented));
+            }
+        }
+        version.files[0].sort_by_key(|file| file.number);
+        for files in &mut version.files[1..] {
+            files.sort_by(|a, b| {
+                version.comparator.compare(&a.smallest, &b.smallest)
+            });
+        }
+        version
+    }
+
+    // the newest version of key in the tables no newer than sequence,
+    // None if no table knows about it, Some(None) if it was deleted
+    pub fn get(
+        &self,
+        key: &[u8],
+        sequence: u64,
+        table_cache: &TableCache,
+        options: &ReadOptions,
+    ) -> Result<Option<Option<Vec<u8>>>, Error> {
+        let seek = internal_key(key, sequence, KIND_FOR_SEEK);
+        let newest_first = self.files[0].iter().rev();
+        let l0 = newest_first
+            .filter(|file| file.contains(self.user_comparator(), key));
+        let sorted = self.files[1..]
+            .iter()
+            .filter_map(|files| self.find_file(files, &seek));
+        for file in l0.chain(sorted) {
+            let covering = self
+                .fragmented_tombstones
+                .get(&file.number)
+                .and_then(|fragmented| {
+                    fragmented.newest_covering(
+                        self.user_comparator(),
+                        key,
+                        sequence,
+                    )
+                });
+            let found = match table_cache.get(file)?.get(&seek, options)? {
+                Some((found, value)) => {
+                    let Some((user_key, found_sequence, kind)) =
+                        parse_internal_key(&found)
+                    else {
+                        return Err(Error::Corruption {
+                            offset: None,
+                            reason: "bad internal key".to_string(),
+                        });
+                    };
+                    let user_comparator = self.user_comparator();
+                    (user_comparator.compare(user_key, key) == Ordering::Equal)
+                        .then_some((found_sequence, kind, value))
+                }
+                None => None,
+            };
+            // older tables only hold older versions, a range deletion
+            // hides those too
+            match found {
+                Some((found_sequence, RecordKind::Value, value))
+                    if covering
+                        .is_none_or(|covering| covering < found_sequence) =>
+                {
+                    return Ok(Some(Some(value)))
+                }
+                Some(_) => return Ok(Some(None)),
+                None if covering.is_some() => return Ok(Some(None)),
+                None => {}
+            }
+        }
+        Ok(None)
+    }
+
+    // a cursor per level 0 table, newest first, then one per sorted
+    // level, the tables only get opened once the cursors get to them
+    pub fn iters(
+        &self,
+        table_cache: &Arc<TableCache>,
+        options: &ReadOptions,
+    ) -> Vec<Source> {
+        self.iters_of(table_cache, options, |_| true)
+    }
+
+    // same, leaving out the tables that can't hold keys starting with
+    // prefix, going by key range and, if there is a filter key, by the
+    // filters of the tables
+    pub fn prefix_iters(
+        &self,
+        table_cache: &Arc<TableCache>,
+        options: &ReadOptions,
+        prefix: &[u8],
+        filter_key: Option<&[u8]>,
+    ) -> Vec<Source> {
+        let user_comparator = self.user_comparator();
+        self.iters_of(table_cache, options, |file| {
+            let smallest = user_key(&file.smallest);
+            let largest = user_key(&file.largest);
+            let in_range = (smallest.starts_with(prefix)
+                || user_comparator.compare(smallest, prefix) == Ordering::Less)
+                && user_comparator.compare(largest, prefix) != Ordering::Less;
+            // a table that fails to open is left in to report it
+            in_range
+                && filter_key.is_none_or(|key| {
+                    table_cache
+                        .get(file)
+                        .map_or(true, |table| table.key_may_match(key))
+                })
+        })
+    }
+
+    fn iters_of(
+        &self,
+        table_cache: &Arc<TableCache>,
+        options: &ReadOptions,
+        keep: impl Fn(&FileMetaData) -> bool,
+    ) -> Vec<Source> {
+        let mut iters: Vec<Source> = vec![];
+        for file in self.files[0].iter().rev().filter(|file| keep(file)) {
+            iters.push(Box::new(self.level_iter(
+                table_cache,
+                options,
+                vec![file.clone()],
+            )));
+        }
+        for files in &self.files[1..] {
+            let files = files
+                .iter()
+                .filter(|file| keep(file))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !files.is_empty() {
+                iters.push(Box::new(self.level_iter(
+                    table_cache,
+                    options,
+                    files,
+                )));
+            }
+        }
+        iters
+    }
+
+    pub fn level_iter(
+        &self,
+        table_cache: &Arc<TableCache>,
+        options: &ReadOptions,
+        files: Vec<Arc<FileMetaData>>,
+    ) -> LevelIterator {
+        LevelIterator {
+            table_cache: table_cache.clone(),
+            options: options.without_snapshot(),
+            comparator: self.comparator.clone(),
+            files,
+            index: 0,
+            table: None,
+            err: None,
+        }
+    }
+
+    // the range tombstones of every table
+    pub fn range_tombstones(&self) -> impl Iterator<Item = &RangeTombstone> {
+        self.files
+            .iter()
+            .flatten()
+            .flat_map(|file| &file.range_tombstones)
+    }
+
+    // true if no level deeper than level could hold a user key in
+    // [start, end]
+    pub fn is_base_level_for_range(
+        &self,
+        level: usize,
+        start: &[u8],
+        end: &[u8],
+    ) -> bool {
+        self.files[level + 1..]
+            .iter()
+            .flatten()
+            .all(|file| !file.overlaps(self.user_comparator(), start, end))
+    }
+
+    // true if no level deeper than level could hold the user key,
+    // so a tombstone for it has nothing left to shadow
+    pub fn is_base_level_for_key(&self, level: usize, key: &[u8]) -> bool {
+        self.is_base_level_for_range(level, key, key)
+    }
+
+    // the tables of level with user keys in [smallest, largest]
+    pub fn overlapping_inputs(
+        &self,
+        level: usize,
+        smallest: &[u8],
+        largest: &[u8],
+    ) -> Vec<Arc<FileMetaData>> {
+        let (mut smallest, mut largest) = (smallest.to_vec(), largest.to_vec());
+        let files = &self.files[level];
+        let mut inputs = vec![];
+        let mut i = 0;
+        while i < files.len() {
+            let file = &files[i];
+            i += 1;
+            let user_comparator = self.user_comparator();
+            if !file.overlaps(user_comparator, &smallest, &largest) {
+                continue;
+            }
+            // level 0 tables overlap each other, leaving one behind
+            // could leave an older entry above a newer one,
+            // so widen the range and start over
+            let file_smallest = user_key(&file.smallest);
+            let file_largest = user_key(&file.largest);
+            let widen_smallest = user_comparator
+                .compare(file_smallest, &smallest)
+                == Ordering::Less;
+            let widen_largest = user_comparator.compare(file_largest, &largest)
+                == Ordering::Greater;
+            if level == 0 && (widen_smallest || widen_largest) {
+                if widen_smallest {
+                    smallest = file_smallest.to_vec();
+                }
+                if widen_largest {
+                    largest = file_largest.to_vec();
+                }
+                inputs.clear();
+                i = 0;
+                continue;
+            }
+            inputs.push(file.clone());
+        }
+        inputs
+    }
+
+    // the level most in need of a compaction and how badly,
+    // a score of 1 or more means it should be compacted
+    pub fn compaction_score(&self, options: &Options) -> (f64, usize) {
+        // level 0 is counted in tables rather than bytes since
+        // every read may have to look at all of them
+        let mut best = (
+            self.files[0].len() as f64
+                / options.level0_compaction_trigger as f64,
+            0,
+        );
+        let mut max_bytes = options.max_bytes_for_level_base as f64;
+        for level in 1..NUM_LEVELS - 1 {
+            let bytes = self.files[level].iter().map(|f| f.size).sum::<u64>();
+            let score = bytes as f64 / max_bytes;
+            if score > best.0 {
+                best = (score, level);
+            }
+            max_bytes *= 10.0;
+        }
+        best
+    }
+
+    // compact_pointers holds the largest internal key of the last
+    // compaction of each level, the next one starts after it so the
+    // whole key space of a level gets its turn
+    pub fn pick_compaction(
+        self: &Arc<Self>,
+        options: &Options,
+        compact_pointers: &[Vec<u8>; NUM_LEVELS],
+    ) -> Option<Compaction> {
+        let (score, level) = self.compaction_score(options);
+        if score < 1.0 {
+            return None;
+        }
+        let files = &self.files[level];
+        let pointer = &compact_pointers[level];
+        let file = files
+            .iter()
+            .find(|file| {
+                pointer.is_empty()
+                    || self.comparator.compare(&file.largest, pointer)
+                        == Ordering::Greater
+            })
+            .unwrap_or(&files[0]);
+        let inputs = if level == 0 {
+            let smallest = user_key(&file.smallest);
+            self.overlapping_inputs(0, smallest, user_key(&file.largest))
+        } else {
+            vec![file.clone()]
+        };
+        Some(self.compaction(level, inputs))
+    }
+
+    // pushes the tables of level among files one level down,
+    // None once none of them are left in it
+    pub fn manual_compaction(
+        self: &Arc<Self>,
+        level: usize,
+        files: &HashSet<u64>,
+    ) -> Option<Compaction> {
+        let inputs = self.files[level]
+            .iter()
+            .filter(|file| files.contains(&file.number))
+            .cloned()
+            .collect::<Vec<_>>();
+        (!inputs.is_empty()).then(|| self.compaction(level, inputs))
+    }
+
+    fn compaction(
+        self: &Arc<Self>,
+        level: usize,
+        inputs: Vec<Arc<FileMetaData>>,
+    ) -> Compaction {
+        let (smallest, largest) = self.key_range(&inputs);
+        let next = self.overlapping_inputs(
+            level + 1,
+            user_key(&smallest),
+            user_key(&largest),
+        );
+        Compaction {
+            version: self.clone(),
+            level,
+            inputs: [inputs, next],
+        }
+    }
+}
+
+// cursor over the entries of sorted tables with disjoint key ranges,
+// one after the other
+pub struct LevelIterator {
+    table_cache: Arc<TableCache>,
+    options: ReadOptions<'static>,
+    comparator: Arc<InternalKeyComparator>,
+    files: Vec<Arc<FileMetaData>>,
+    // the table the cursor is in
+    index: usize,
+    // None past either end
+    table: Option<TableIterator>,
+    err: Option<Error>,
+}
+
+impl LevelIterator {
+    fn open_table(&mut self, index: usize) {
+        self.index = index;
+        self.table = None;
+        let Some(file) = self.files.get(index) else {
+            return;
+        };
+        match self.table_cache.get(file) {
+            Ok(table) => self.table = Some(table.iter(&self.options)),
+            Err(e) => self.err = Some(e),
+        }
+    }
+
+    // moves on to the next table until there is an entry to be at,
+    // or back to the previous one when going backwards
+    fn skip_empty_tables(&mut self, backwards: bool) {
+        while let Some(table) = &mut self.table {
+            if table.valid() {
+                return;
+            }
+            if let Err(e) = table.status() {
+                self.err = Some(e);
+                self.table = None;
+                return;
+            }
+            if backwards {
+                let Some(index) = self.index.checked_sub(1) else {
+                    self.table = None;
+                    return;
+                };
+                self.open_table(index);
+                if let Some(table) = &mut self.table {
+                    table.seek_to_last();
+                }
+            } else {
+                self.open_table(self.index + 1);
+                if let Some(table) = &mut self.table {
+                    table.seek_to_first();
+                }
+            }
+        }
+    }
+}
+
+impl InternalIterator for LevelIterator {
+    fn valid(&self) -> bool {
+        self.table.as_ref().is_some_and(TableIterator::valid)
+    }
+
+    fn key(&self) -> &[u8] {
+        self.table.as_ref().unwrap().key()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.table.as_ref().unwrap().value()
+    }
+
+    fn seek_to_first(&mut self) {
+        self.open_table(0);
+        if let Some(table) = &mut self.table {
+            table.seek_to_first();
+        }
+        self.skip_empty_tables(false);
+    }
+
+    fn seek_to_last(&mut self) {
+        let Some(last) = self.files.len().checked_sub(1) else {
+            self.table = None;
+            return;
+        };
+        self.open_table(last);
+        if let Some(table) = &mut self.table {
+            table.seek_to_last();
+        }
+        self.skip_empty_tables(true);
+    }
+
+    fn seek(&mut self, target: &[u8]) {
+        let index = self.files.partition_point(|file| {
+            self.comparator.compare(&file.largest, target) == Ordering::Less
+        });
+        self.open_table(index);
+        if let Some(table) = &mut self.table {
+            table.seek(target);
+        }
+        self.skip_empty_tables(false);
+    }
+
+    fn next(&mut self) {
+        self.table.as_mut().unwrap().next();
+        self.skip_empty_tables(false);
+    }
+
+    fn prev(&mut self) {
+        self.table.as_mut().unwrap().prev();
+        self.skip_empty_tables(true);
+    }
+
+    fn status(&mut self) -> Result<(), Error> {
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+        match &mut self.table {
+            Some(table) => table.status(),
+            None => Ok(()),
+        }
+    }
+}
+
+// merges tables of level with the ones of level + 1 they overlap,
+// the output goes to level + 1
+pub struct Compaction {
+    // the version the inputs were picked from
+    pub version: Arc<Version>,
+    pub level: usize,
+    pub inputs: [Vec<Arc<FileMetaData>>; 2],
+}
+
+impl Compaction {
+    // a lone table overlapping nothing below can just change levels
+    pub fn is_trivial_move(&self) -> bool {
+        self.inputs[0].len() == 1 && self.inputs[1].is_empty()
+    }
+
+    pub fn largest(&self) -> Vec<u8> {
+        self.version.key_range(&self.inputs[0]).1
+    }
+
+    // drops the inputs, the outputs are added by the caller
+    pub fn edit(&self) -> VersionEdit {
+        let mut edit = VersionEdit::default();
+        edit.compact_pointers.push((self.level, self.largest()));
+        for (i, inputs) in self.inputs.iter().enumerate() {
+            for file in inputs {
+                edit.deleted.push((self.level + i, file.number));
+            }
+        }
+        edit
+    }
+}
+
+// the current version along with the file numbers in use, every
+// change to it goes to the manifest before it takes effect
+pub struct VersionSet {
+    dir: PathBuf,
+    options: Options,
+    comparator: Arc<InternalKeyComparator>,
+    current: Arc<Version>,
+    // versions handed out before the current one, the tables of
+    // those still in use must stay around
+    old_versions: Vec<Weak<Version>>,
+    next_file_number: u64,
+    manifest_number: u64,
+    log_number: u64,
+    last_sequence: u64,
+    // None until the first edit after opening starts a new manifest
+    manifest: Option<log::Writer>,
+    compact_pointers: [Vec<u8>; NUM_LEVELS],
+    // the manifest recovered from ends in a torn record, the tables
+    // no version lists may be what its lost edit added
+    torn_manifest: bool,
+}
+
+impl VersionSet {
+    pub fn new(
+        dir: PathBuf,
+        options: Options,
+        comparator: Arc<InternalKeyComparator>,
+    ) -> Self {
+        Self {
+            dir,
+            options,
+            current: Arc::new(Version::new(comparator.clone())),
+            comparator,
+            old_versions: vec![],
+            next_file_number: 1,
+            manifest_number: 0,
+            log_number: 0,
+            last_sequence: 0,
+            manifest: None,
+            compact_pointers: Default::default(),
+            torn_manifest: false,
+        }
+    }
+
+    // replays the manifest CURRENT names,
+    // false if there is no CURRENT to begin with
+    //
+    // a manifest is synced after every edit, so only its last record
+    // may be torn, whatever the options, any other bad record is
+    // returned as the corruption it is
+    pub fn recover(&mut self) -> Result<bool, Error> {
+        let Some(manifest_number) = filename::read_current_file(&self.dir)?
+        else {
+            return Ok(false);
+        };
+        let path = file_name(&self.dir, manifest_number, FileType::Manifest);
+        let mut version = Version::new(self.comparator.clone());
+        let mut next_file_number = None;
+        let mut last_sequence = None;
+        let complete = log::replay(&path, |record| {
+            let edit = VersionEdit::decode(&record)?;
+            let user_comparator = self.comparator.user_comparator();
+            if let Some(name) = &edit.comparator {
+                if name != user_comparator.name() {
+                    return Err(Error::InvalidArgument(format!(
+                        "comparator {name} does not match {}",
+                        user_comparator.name()
+                    )));
+                }
+            }
+            version = version.apply(&edit);
+            for (level, key) in edit.compact_pointers {
+                self.compact_pointers[level] = key;
+            }
+            self.log_number = edit.log_number.unwrap_or(self.log_number);
+            next_file_number = edit.next_file_number.or(next_file_number);
+            last_sequence = edit.last_sequence.or(last_sequence);
+            Ok(())
+        })?;
+        let missing = |field: &str| Error::Corruption {
+            offset: None,
+            reason: format!("manifest without a {field}"),
+        };
+        let next_file_number =
+            next_file_number.ok_or_else(|| missing("next file number"))?;
+        self.last_sequence =
+            last_sequence.ok_or_else(|| missing("last sequence"))?;
+        self.next_file_number = next_file_number.max(manifest_number + 1);
+        self.torn_manifest = !complete;
+        self.append_version(version);
+        Ok(true)
+    }
+
+    pub fn current(&self) -> Arc<Version> {
+        self.current.clone()
+    }
+
+    pub fn new_file_number(&mut self) -> u64 {
+        let number = self.next_file_number;
+        self.next_file_number += 1;
+        number
+    }
+
+    pub fn mark_file_number_used(&mut self, number: u64) {
+        self.next_file_number = self.next_file_number.max(number + 1);
+    }
+
+    pub fn log_number(&self) -> u64 {
+        self.log_number
+    }
+
+    pub fn manifest_number(&self) -> u64 {
+        self.manifest_number
+    }
+
+    // whether tables no version refers to have to stay, until an open
+    // recovers from a whole manifest
+    pub fn torn_manifest(&self) -> bool {
+        self.torn_manifest
+    }
+
+    // the sequence number of the last write
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    pub fn set_last_sequence(&mut self, sequence: u64) {
+        debug_assert!(sequence >= self.last_sequence);
+        self.last_sequence = sequence;
+    }
+
+    // the tables of every version still in use
+    pub fn live_files(&mut self) -> HashSet<u64> {
+        self.old_versions
+            .retain(|version| version.strong_count() > 0);
+        let versions = self.old_versions.iter().filter_map(Weak::upgrade);
+        let mut live = HashSet::new();
+        for version in versions.chain([self.current.clone()]) {
+            for files in &version.files {
+                live.extend(files.iter().map(|file| file.number));
+            }
+        }
+        live
+    }
+
+    // writes edit to the manifest and makes the version it leads to
+    // the current one
+    pub fn log_and_apply(
+        &mut self,
+        edit: &mut VersionEdit,
+    ) -> Result<(), Error> {
+        edit.log_number.get_or_insert(self.log_number);
+        let version = self.current.apply(edit);
+        if let Err(e) = self.write_manifest(edit) {
+            // whatever made it to the manifest may be half a record,
+            // the next edit starts a new one
+            self.manifest = None;
+            return Err(e);
+        }
+        self.log_number = edit.log_number.unwrap();
+        self.append_version(version);
+        Ok(())
+    }
+
+    fn write_manifest(&mut self, edit: &mut VersionEdit) -> Result<(), Error> {
+        let new_manifest = self.manifest.is_none();
+        if new_manifest {
+            // starts out with everything the current version holds
+            self.manifest_number = self.new_file_number();
+            let path =
+                file_name(&self.dir, self.manifest_number, FileType::Manifest);
+            let mut manifest = log::Writer::create(&path)?;
+            manifest.add_record(&self.snapshot().encode())?;
+            self.manifest = Some(manifest);
+        }
+        edit.next_file_number = Some(self.next_file_number);
+        edit.last_sequence = Some(self.last_sequence);
+        let manifest = self.manifest.as_mut().unwrap();
+        manifest.add_record(&edit.encode())?;
+        manifest.sync()?;
+        if new_manifest {
+            filename::set_current_file(&self.dir, self.manifest_number)?;
+        }
+        Ok(())
+    }
+
+    fn snapshot(&self) -> VersionEdit {
+        let mut edit = VersionEdit::default();
+        let user_comparator = self.comparator.user_comparator();
+        edit.comparator = Some(user_comparator.name().to_string());
+        for (level, key) in self.compact_pointers.iter().enumerate() {
+            if !key.is_empty() {
+                edit.compact_pointers.push((level, key.clone()));
+            }
+        }
+        for (level, files) in self.current.files.iter().enumerate() {
+            for file in files {
+                edit.added.push((level, file.clone()));
+            }
+        }
+        edit
+    }
+
+    fn append_version(&mut self, version: Version) {
+        let old = std::mem::replace(&mut self.current, Arc::new(version));
+        self.old_versions.push(Arc::downgrade(&old));
+    }
+
+    #[cfg(test)]
+    pub fn needs_compaction(&self) -> bool {
+        self.current.compaction_score(&self.options).0 >= 1.0
+    }
+
+    pub fn pick_compaction(&mut self) -> Option<Compaction> {
+        let compaction = self
+            .current
+            .pick_compaction(&self.options, &self.compact_pointers)?;
+        self.compact_pointers[compaction.level] = compaction.largest();
+        Some(compaction)
+    }
+}
@@ -0,0 +1,265 @@
+use crate::coding;
+use crate::comparator::Comparator;
+use crate::dbformat::user_key;
+use crate::range_del::RangeTombstone;
+use crate::version::NUM_LEVELS;
+use crate::Error;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetaData {
+    pub number: u64,
+    pub size: u64,
+    // internal keys, covering the range tombstones too
+    pub smallest: Vec<u8>,
+    pub largest: Vec<u8>,
+    pub range_tombstones: Vec<RangeTombstone>,
+}
+
+impl FileMetaData {
+    // whether some version of the user key may be in the table
+    pub fn contains(&self, comparator: &dyn Comparator, key: &[u8]) -> bool {
+        self.overlaps(comparator, key, key)
+    }
+
+    // whether the table holds user keys in [smallest, largest]
+    pub fn overlaps(
+        &self,
+        comparator: &dyn Comparator,
+        smallest: &[u8],
+        largest: &[u8],
+    ) -> bool {
+        comparator.compare(user_key(&self.smallest), largest)
+            != Ordering::Greater
+            && comparator.compare(smallest, user_key(&self.largest))
+                != Ordering::Greater
+    }
+}
+
+// every field of an encoded edit starts with one of these,
+// same numbers as leveldb
+const TAG_COMPARATOR: u64 = 1;
+const TAG_LOG_NUMBER: u64 = 2;
+const TAG_NEXT_FILE_NUMBER: u64 = 3;
+const TAG_LAST_SEQUENCE: u64 = 4;
+const TAG_COMPACT_POINTER: u64 = 5;
+const TAG_DELETED_FILE: u64 = 6;
+const TAG_NEW_FILE: u64 = 7;
+// a range tombstone of the file added right before it
+const TAG_RANGE_TOMBSTONE: u64 = 10;
+
+// a change to the set of live files, the manifest is a log of these
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VersionEdit {
+    // name of the comparator the keys are ordered by
+    pub comparator: Option<String>,
+    // logs older than this one are no longer needed
+    pub log_number: Option<u64>,
+    pub next_file_number: Option<u64>,
+    // the sequence number of the last write in the tables
+    pub last_sequence: Option<u64>,
+    pub compact_pointers: Vec<(usize, Vec<u8>)>,
+    pub deleted: Vec<(usize, u64)>,
+    pub added: Vec<(usize, Arc<FileMetaData>)>,
+}
+
+impl VersionEdit {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut dst = vec![];
+        if let Some(name) = &self.comparator {
+            coding::put_varint(&mut dst, TAG_COMPARATOR);
+            coding::put_length_prefixed(&mut dst, name.as_bytes());
+        }
+        if let Some(number) = self.log_number {
+            coding::put_varint(&mut dst, TAG_LOG_NUMBER);
+            coding::put_varint(&mut dst, number);
+        }
+        if let Some(number) = self.next_file_number {
+            coding::put_varint(&mut dst, TAG_NEXT_FILE_NUMBER);
+            coding::put_varint(&mut dst, number);
+        }
+        if let Some(sequence) = self.last_sequence {
+            coding::put_varint(&mut dst, TAG_LAST_SEQUENCE);
+            coding::put_varint(&mut dst, sequence);
+        }
+        for (level, key) in &self.compact_pointers {
+            coding::put_varint(&mut dst, TAG_COMPACT_POINTER);
+            coding::put_varint(&mut dst, *level as u64);
+            coding::put_length_prefixed(&mut dst, key);
+        }
+        for (level, number) in &self.deleted {
+            coding::put_varint(&mut dst, TAG_DELETED_FILE);
+            coding::put_varint(&mut dst, *level as u64);
+            coding::put_varint(&mut dst, *number);
+        }
+        for (level, file) in &self.added {
+            coding::put_varint(&mut dst, TAG_NEW_FILE);
+            coding::put_varint(&mut dst, *level as u64);
+            coding::put_varint(&mut dst, file.number);
+            coding::put_varint(&mut dst, file.size);
+            coding::put_length_prefixed(&mut dst, &file.smallest);
+            coding::put_length_prefixed(&mut dst, &file.largest);
+            for tombstone in &file.range_tombstones {
+                coding::put_varint(&mut dst, TAG_RANGE_TOMBSTONE);
+                coding::put_varint(&mut dst, tombstone.sequence);
+                coding::put_length_prefixed(&mut dst, &tombstone.start);
+                coding::put_length_prefixed(&mut dst, &tombstone.end);
+            }
+        }
+        dst
+    }
+
+    pub fn decode(src: &[u8]) -> Result<Self, Error> {
+        let corruption = |reason: &str| Error::Corruption {
+            offset: None,
+            reason: format!("bad version edit: {reason}"),
+        };
+        let mut input = src;
+        let mut edit = Self::default();
+        while !input.is_empty() {
+            let tag =
+                get_number(&mut input).ok_or_else(|| corruption("tag"))?;
+            match tag {
+                TAG_COMPARATOR => {
+                    let name = get_key(&mut input)
+                        .and_then(|name| String::from_utf8(name).ok())
+                        .ok_or_else(|| corruption("comparator name"))?;
+                    edit.comparator = Some(name);
+                }
+                TAG_LOG_NUMBER => {
+                    let number = get_number(&mut input)
+                        .ok_or_else(|| corruption("log number"))?;
+                    edit.log_number = Some(number);
+                }
+                TAG_NEXT_FILE_NUMBER => {
+                    let number = get_number(&mut input)
+                        .ok_or_else(|| corruption("next file number"))?;
+                    edit.next_file_number = Some(number);
+                }
+                TAG_LAST_SEQUENCE => {
+                    let sequence = get_number(&mut input)
+                        .ok_or_else(|| corruption("last sequence"))?;
+                    edit.last_sequence = Some(sequence);
+                }
+                TAG_COMPACT_POINTER => {
+                    let level = get_level(&mut input)
+                        .ok_or_else(|| corruption("compact pointer"))?;
+                    let key = get_key(&mut input)
+                        .ok_or_else(|| corruption("compact pointer"))?;
+                    edit.compact_pointers.push((level, key));
+                }
+                TAG_DELETED_FILE => {
+                    let file =
+                        get_level(&mut input).zip(get_number(&mut input));
+                    edit.deleted
+                        .push(file.ok_or_else(|| corruption("deleted file"))?);
+                }
+                TAG_NEW_FILE => {
+                    let file = get_level(&mut input).and_then(|level| {
+                        let file = FileMetaData {
+                            number: get_number(&mut input)?,
+                            size: get_number(&mut input)?,
+                            smallest: get_key(&mut input)?,
+                            largest: get_key(&mut input)?,
+                            range_tombstones: vec![],
+                        };
+                        Some((level, Arc::new(file)))
+                    });
+                    edit.added
+                        .push(file.ok_or_else(|| corruption("new file"))?);
+                }
+                TAG_RANGE_TOMBSTONE => {
+                    let tombstone =
+                        get_number(&mut input).and_then(|sequence| {
+                            Some(RangeTombstone {
+                                sequence,
+                                start: get_key(&mut input)?,
+                                end: get_key(&mut input)?,
+                            })
+                        });
+                    let tombstone = tombstone
+                        .ok_or_else(|| corruption("range tombstone"))?;
+                    // the file was only just decoded, nothing else has it
+                    let file = edit
+                        .added
+                        .last_mut()
+                        .and_then(|(_, file)| Arc::get_mut(file))
+                        .ok_or_else(|| corruption("range tombstone"))?;
+                    file.range_tombstones.push(tombstone);
+                }
+                _ => return Err(corruption("unknown tag")),
+            }
+        }
+        Ok(edit)
+    }
+}
+
+fn get_number(input: &mut &[u8]) -> Option<u64> {
+    let (v, n) = coding::get_varint(input)?;
+    *input = &input[n..];
+    Some(v)
+}
+
+fn get_level(input: &mut &[u8]) -> Option<usize> {
+    let level = get_number(input)?;
+    (level < NUM_LEVELS as u64).then_some(level as usize)
+}
+
+fn get_key(input: &mut &[u8]) -> Option<Vec<u8>> {
+    let (key, n) = coding::get_length_prefixed(input)?;
+    let key = key.to_vec();
+    *input = &input[n..];
+    Some(key)
+}
+
+#[test]
+fn test_version_edit() {
+    let file = |number, smallest: &[u8], largest: &[u8]| {
+        Arc::new(FileMetaData {
+            number,
+            size: number * 100,
+            smallest: smallest.to_vec(),
+            largest: largest.to_vec(),
+            range_tombstones: vec![],
+        })
+    };
+    let mut with_tombstones = file(8, b"c", b"x");
+    Arc::get_mut(&mut with_tombstones).unwrap().range_tombstones = vec![
+        RangeTombstone {
+            sequence: 10,
+            start: b"c".to_vec(),
+            end: b"m".to_vec(),
+        },
+        RangeTombstone {
+            sequence: 12,
+            start: b"e".to_vec(),
+            end: b"x".to_vec(),
+        },
+    ];
+    let edit = VersionEdit {
+        comparator: Some("reberu.BytewiseComparator".to_string()),
+        log_number: Some(7),
+        next_file_number: Some(9),
+        last_sequence: Some(1234),
+        compact_pointers: vec![(1, b"m".to_vec())],
+        deleted: vec![(0, 3), (1, 4)],
+        added: vec![
+            (1, file(5, b"a", b"k")),
+            (2, with_tombstones),
+            (2, file(6, b"", b"\xff")),
+        ],
+    };
+    let encoded = edit.encode();
+    assert_eq!(VersionEdit::decode(&encoded).unwrap(), edit);
+    assert_eq!(VersionEdit::decode(&[]).unwrap(), VersionEdit::default());
+    assert!(VersionEdit::decode(&encoded[..encoded.len() - 1]).is_err());
+    assert!(VersionEdit::decode(&[99]).is_err());
+    // a range tombstone needs a file to go with
+    let mut orphan = vec![];
+    coding::put_varint(&mut orphan, TAG_RANGE_TOMBSTONE);
+    coding::put_varint(&mut orphan, 1);
+    coding::put_length_prefixed(&mut orphan, b"a");
+    coding::put_length_prefixed(&mut orphan, b"b");
+    assert!(VersionEdit::decode(&orphan).is_err());
+}
@@ -0,0 +1,192 @@
+use crate::coding;
+use crate::dbformat::RecordKind;
+use crate::Error;
+
+// sequence number of the first record (u64) and number of records (u32)
+const HEADER_LEN: usize = 12;
+
+// the kind of a range deletion record, past the ones of RecordKind
+// since it never ends up in an internal key
+const RANGE_DELETION: u8 = 2;
+
+// a record of a batch, as added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchRecord<'a> {
+    Put(&'a [u8], &'a [u8]),
+    Delete(&'a [u8]),
+    // start, end
+    DeleteRange(&'a [u8], &'a [u8]),
+}
+
+// a set of updates applied to the database all at once
+//
+// serialized as a header followed by the records:
+// kind | varint key len | key [| varint value len | value]
+// where values carry a value and range deletions the end of the range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteBatch {
+    rep: Vec<u8>,
+}
+
+impl Default for WriteBatch {
+    fn default() -> Self {
+        Self {
+            rep: vec![0; HEADER_LEN],
+        }
+    }
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.set_count(self.len() + 1);
+        self.rep.push(RecordKind::Value as u8);
+        coding::put_length_prefixed(&mut self.rep, key);
+        coding::put_length_prefixed(&mut self.rep, value);
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.set_count(self.len() + 1);
+        self.rep.push(RecordKind::Deletion as u8);
+        coding::put_length_prefixed(&mut self.rep, key);
+    }
+
+    // every key in [start, end)
+    pub fn delete_range(&mut self, start: &[u8], end: &[u8]) {
+        self.set_count(self.len() + 1);
+        self.rep.push(RANGE_DELETION);
+        coding::put_length_prefixed(&mut self.rep, start);
+        coding::put_length_prefixed(&mut self.rep, end);
+    }
+
+    // adds the records of other after the ones of self
+    pub fn append(&mut self, other: &WriteBatch) {
+        self.set_count(self.len() + other.len());
+        self.rep.extend_from_slice(&other.rep[HEADER_LEN..]);
+    }
+
+    pub fn clear(&mut self) {
+        self.rep.clear();
+        self.rep.resize(HEADER_LEN, 0);
+    }
+
+    // number of records
+    pub fn len(&self) -> usize {
+        u32::from_le_bytes(self.rep[8..HEADER_LEN].try_into().unwrap()) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn set_count(&mut self, count: usize) {
+        self.rep[8..HEADER_LEN].copy_from_slice(&(count as u32).to_le_bytes());
+    }
+
+    // the records are numbered from this one on,
+    // set by the database when the batch is written
+    pub(crate) fn sequence(&self) -> u64 {
+        u64::from_le_bytes(self.rep[..8].try_into().unwrap())
+    }
+
+    pub(crate) fn set_sequence(&mut self, sequence: u64) {
+        self.rep[..8].copy_from_slice(&sequence.to_le_bytes());
+    }
+
+    // the serialized batch
+    pub fn contents(&self) -> &[u8] {
+        &self.rep
+    }
+
+    // takes a serialized batch, checking every record decodes
+    // and the count matches
+    pub fn from_contents(rep: Vec<u8>) -> Result<Self, Error> {
+        let corruption = || Error::Corruption {
+            offset: None,
+            reason: "bad write batch".to_string(),
+        };
+        if rep.len() < HEADER_LEN {
+            return Err(corruption());
+        }
+        let batch = Self { rep };
+        let mut count = 0;
+        let mut input = &batch.rep[HEADER_LEN..];
+        while !input.is_empty() {
+            decode_record(&mut input).ok_or_else(corruption)?;
+            count += 1;
+        }
+        if count != batch.len() {
+            return Err(corruption());
+        }
+        Ok(batch)
+    }
+
+    // the records in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = BatchRecord<'_>> {
+        let mut input = &self.rep[HEADER_LEN..];
+        // only ever built by put and delete or checked by from_contents
+        std::iter::from_fn(move || {
+            (!input.is_empty()).then(|| decode_record(&mut input).unwrap())
+        })
+    }
+}
+
+fn decode_record<'a>(input: &mut &'a [u8]) -> Option<BatchRecord<'a>> {
+    let (kind, rest) = input.split_first()?;
+    let (key, n) = coding::get_length_prefixed(rest)?;
+    let mut rest = &rest[n..];
+    let mut get_value = || {
+        let (value, n) = coding::get_length_prefixed(rest)?;
+        rest = &rest[n..];
+        Some(value)
+    };
+    let record = match *kind {
+        RANGE_DELETION => BatchRecord::DeleteRange(key, get_value()?),
+        kind => match RecordKind::from_u8(kind)? {
+            RecordKind::Value => BatchRecord::Put(key, get_value()?),
+            RecordKind::Deletion => BatchRecord::Delete(key),
+        },
+    };
+    *input = rest;
+    Some(record)
+}
+
+#[test]
+fn test_write_batch() {
+    let mut batch = WriteBatch::new();
+    assert!(batch.is_empty());
+    batch.put(b"foo", b"bar");
+    batch.delete(b"box");
+    batch.put(b"baz", b"");
+    batch.delete_range(b"a", b"c");
+    assert_eq!(batch.len(), 4);
+    assert_eq!(
+        batch.iter().collect::<Vec<_>>(),
+        [
+            BatchRecord::Put(b"foo", b"bar"),
+            BatchRecord::Delete(b"box"),
+            BatchRecord::Put(b"baz", b""),
+            BatchRecord::DeleteRange(b"a", b"c"),
+        ]
+    );
+
+    let decoded = WriteBatch::from_contents(batch.contents().to_vec());
+    assert_eq!(decoded.unwrap(), batch);
+    batch.set_sequence(100);
+    assert_eq!(batch.sequence(), 100);
+    assert_eq!(batch.len(), 4);
+    let contents = batch.contents();
+    for bad in [&contents[..2], &contents[..contents.len() - 1]] {
+        assert!(WriteBatch::from_contents(bad.to_vec()).is_err());
+    }
+    let mut wrong_count = contents.to_vec();
+    wrong_count[8] = 2;
+    assert!(WriteBatch::from_contents(wrong_count).is_err());
+
+    batch.clear();
+    assert!(batch.is_empty());
+    assert_eq!(batch.iter().count(), 0);
+}